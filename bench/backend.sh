@@ -0,0 +1,39 @@
+#!/bin/sh
+# Compare I/O backends and buffer sizes by hashing a generated tree.
+# usage: bench/backend.sh [<dir>] [<feature>]
+# <dir> should be on the filesystem of interest, O_DIRECT falls back to
+# regular read on filesystems without support, e.g. tmpfs.
+
+set -e
+
+DIR=${1:-./bench.tmp}
+FEATURE=${2:-squash1}
+BIN=./target/release/dirhash-rs
+
+cargo build --release --features=$FEATURE
+
+if [ ! -d "$DIR" ]; then
+	mkdir -p "$DIR/small" "$DIR/large"
+	i=0
+	while [ $i -lt 2000 ]; do
+		head -c 4096 /dev/urandom > "$DIR/small/$i"
+		i=$((i + 1))
+	done
+	i=0
+	while [ $i -lt 2 ]; do
+		head -c 16777216 /dev/urandom > "$DIR/large/$i"
+		i=$((i + 1))
+	done
+fi
+
+for backend in read mmap direct; do
+	for size in 4096 65536 1048576; do
+		for fadvise in "" "--fadvise"; do
+			start=$(date +%s.%N)
+			sum=$($BIN --squash --io_backend $backend --buffer_size $size $fadvise "$DIR")
+			end=$(date +%s.%N)
+			printf "%-6s %8s %-9s %8.3fs %s\n" $backend $size "${fadvise:--}" \
+				$(awk "BEGIN { print $end - $start }") "$sum"
+		done
+	done
+done
@@ -0,0 +1,524 @@
+use crate::hash;
+use crate::util;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ArchiveType {
+    Tar,
+    TarGz,
+    TarZst,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveType::Tar => "tar",
+            ArchiveType::TarGz => "tar.gz",
+            ArchiveType::TarZst => "tar.zst",
+            ArchiveType::TarXz => "tar.xz",
+            ArchiveType::Zip => "zip",
+        }
+    }
+}
+
+// archive type is determined by file name, not content
+pub(crate) fn get_archive_type(f: &str) -> Option<ArchiveType> {
+    let f = f.to_lowercase();
+    if f.ends_with(".tar") {
+        Some(ArchiveType::Tar)
+    } else if f.ends_with(".tar.gz") || f.ends_with(".tgz") {
+        Some(ArchiveType::TarGz)
+    } else if f.ends_with(".tar.zst") || f.ends_with(".tzst") {
+        Some(ArchiveType::TarZst)
+    } else if f.ends_with(".tar.xz") || f.ends_with(".txz") {
+        Some(ArchiveType::TarXz)
+    } else if [".zip", ".jar", ".war", ".ear", ".whl", ".apk"]
+        .iter()
+        .any(|x| f.ends_with(x))
+    {
+        Some(ArchiveType::Zip)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Member {
+    pub(crate) f: String, // relative to archive root
+    pub(crate) t: util::FileType,
+    pub(crate) sum: Vec<Vec<u8>>, // regular file only
+    pub(crate) written: u64,      // regular file only
+    pub(crate) nlink: u64,        // regular file only, links within archive
+}
+
+fn open_archive(f: &str, t: &ArchiveType) -> std::io::Result<Box<dyn std::io::Read>> {
+    let r = std::io::BufReader::new(std::fs::File::open(f)?);
+    Ok(match t {
+        ArchiveType::Tar => Box::new(r),
+        ArchiveType::TarGz => Box::new(flate2::read::MultiGzDecoder::new(r)),
+        ArchiveType::TarZst => Box::new(zstd::stream::read::Decoder::with_buffer(r)?),
+        ArchiveType::TarXz => Box::new(xz2::read::XzDecoder::new_multi_decoder(r)),
+        ArchiveType::Zip => panic!("{}", t.as_str()),
+    })
+}
+
+// drop ".", "..", leading "/" and first strip components
+pub(crate) fn get_member_path(f: &std::path::Path, strip: usize) -> std::io::Result<String> {
+    let mut l = vec![];
+    for x in f.components() {
+        if let std::path::Component::Normal(x) = x {
+            let Some(x) = x.to_str() else {
+                return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+            };
+            l.push(x);
+        }
+    }
+    Ok(l.into_iter().skip(strip).collect::<Vec<&str>>().join("/"))
+}
+
+// read archive members without extracting to disk
+pub(crate) fn read_archive(
+    f: &str,
+    t: &ArchiveType,
+    hash_algo: &[String],
+    strip: usize,
+) -> std::io::Result<Vec<Member>> {
+    match t {
+        ArchiveType::Zip => read_zip(f, hash_algo, strip),
+        _ => read_tar(f, t, hash_algo, strip),
+    }
+}
+
+fn read_tar(
+    f: &str,
+    t: &ArchiveType,
+    hash_algo: &[String],
+    strip: usize,
+) -> std::io::Result<Vec<Member>> {
+    let mut l: Vec<Member> = vec![];
+    let mut reg = std::collections::HashMap::new(); // for hardlinks
+    let mut lnk = vec![]; // hardlink and its target
+    let mut a = tar::Archive::new(open_archive(f, t)?);
+
+    for entry in a.entries()? {
+        let mut entry = entry?;
+        let f = get_member_path(&entry.path()?, strip)?;
+        if f.is_empty() {
+            continue; // archive root or stripped
+        }
+        let x = match entry.header().entry_type() {
+            tar::EntryType::Directory => Member {
+                f,
+                t: util::FileType::Dir,
+                sum: vec![],
+                written: 0,
+                nlink: 0,
+            },
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mut r = std::io::BufReader::new(&mut entry);
+                let (sum, written) = hash::get_hash_multi(&mut r, hash_algo)?;
+                reg.insert(f.clone(), l.len());
+                Member {
+                    f,
+                    t: util::FileType::Reg,
+                    sum,
+                    written,
+                    nlink: 1,
+                }
+            }
+            // hardlink has no data, take the one from its target
+            tar::EntryType::Link => {
+                let target = match entry.link_name()? {
+                    Some(v) => get_member_path(&v, strip)?,
+                    None => String::new(),
+                };
+                match reg.get(&target).copied() {
+                    Some(i) => {
+                        reg.insert(f.clone(), i);
+                        lnk.push((l.len(), i));
+                        l[i].nlink += 1;
+                        Member {
+                            f,
+                            t: util::FileType::Reg,
+                            sum: l[i].sum.clone(),
+                            written: l[i].written,
+                            nlink: 0, // counted below
+                        }
+                    }
+                    None => Member {
+                        f,
+                        t: util::FileType::Invalid,
+                        sum: vec![],
+                        written: 0,
+                        nlink: 0,
+                    },
+                }
+            }
+            tar::EntryType::Symlink => Member {
+                f,
+                t: util::FileType::Symlink,
+                sum: vec![],
+                written: 0,
+                nlink: 0,
+            },
+            // device nodes have no data in archive
+            tar::EntryType::Char | tar::EntryType::Block | tar::EntryType::Fifo => Member {
+                f,
+                t: util::FileType::Unsupported,
+                sum: vec![],
+                written: 0,
+                nlink: 0,
+            },
+            _ => continue, // extension headers
+        };
+        l.push(x);
+    }
+
+    // link count of each member of hardlink group as if extracted
+    for (i, j) in lnk {
+        l[i].nlink = l[j].nlink;
+    }
+    Ok(l)
+}
+
+// only member data is hashed, hence timestamps and compression method or
+// level don't affect the result
+fn read_zip(f: &str, hash_algo: &[String], strip: usize) -> std::io::Result<Vec<Member>> {
+    let mut l: Vec<Member> = vec![];
+    let mut dir = std::collections::HashSet::new();
+    let mut a = zip::ZipArchive::new(std::fs::File::open(f)?)?;
+
+    for i in 0..a.len() {
+        let mut entry = a.by_index(i)?;
+        let name = entry.name()?.to_string();
+        let f = get_member_path(std::path::Path::new(&name), strip)?;
+        if f.is_empty() {
+            continue; // archive root or stripped
+        }
+
+        // zip often lacks directory entries, add implied ones first
+        let v: Vec<&str> = f.split('/').collect();
+        for j in 1..v.len() {
+            let d = v[..j].join("/");
+            if dir.insert(d.clone()) {
+                l.push(Member {
+                    f: d,
+                    t: util::FileType::Dir,
+                    sum: vec![],
+                    written: 0,
+                    nlink: 0,
+                });
+            }
+        }
+
+        let x = if entry.is_dir() {
+            if !dir.insert(f.clone()) {
+                continue;
+            }
+            Member {
+                f,
+                t: util::FileType::Dir,
+                sum: vec![],
+                written: 0,
+                nlink: 0,
+            }
+        } else if entry.is_symlink() {
+            Member {
+                f,
+                t: util::FileType::Symlink,
+                sum: vec![],
+                written: 0,
+                nlink: 0,
+            }
+        } else {
+            let mut r = std::io::BufReader::new(&mut entry);
+            let (sum, written) = hash::get_hash_multi(&mut r, hash_algo)?;
+            Member {
+                f,
+                t: util::FileType::Reg,
+                sum,
+                written,
+                nlink: 1,
+            }
+        };
+        l.push(x);
+    }
+    Ok(l)
+}
+
+#[cfg(test)]
+mod tests {
+    fn append_entry(
+        b: &mut tar::Builder<Vec<u8>>,
+        f: &str,
+        t: tar::EntryType,
+        data: &[u8],
+        link: &str,
+    ) {
+        let mut h = tar::Header::new_gnu();
+        h.set_entry_type(t);
+        h.set_size(data.len().try_into().unwrap());
+        h.set_mode(0o644);
+        if !link.is_empty() {
+            h.set_link_name(link).unwrap();
+        }
+        b.append_data(&mut h, f, data).unwrap();
+    }
+
+    #[test]
+    fn test_get_archive_type() {
+        let archive_list = [
+            ("x.tar", Some(super::ArchiveType::Tar)),
+            ("/path/to/x.tar.gz", Some(super::ArchiveType::TarGz)),
+            ("x.TGZ", Some(super::ArchiveType::TarGz)),
+            ("x.tar.zst", Some(super::ArchiveType::TarZst)),
+            ("x.tzst", Some(super::ArchiveType::TarZst)),
+            ("x.tar.xz", Some(super::ArchiveType::TarXz)),
+            ("x.txz", Some(super::ArchiveType::TarXz)),
+            ("x.zip", Some(super::ArchiveType::Zip)),
+            ("x.jar", Some(super::ArchiveType::Zip)),
+            ("x-1.0-py3-none-any.whl", Some(super::ArchiveType::Zip)),
+            ("x", None),
+            ("x.gz", None),
+            ("x.tar.bz2", None),
+        ];
+        for x in &archive_list {
+            assert_eq!(super::get_archive_type(x.0), x.1, "{x:?}");
+        }
+    }
+
+    #[test]
+    fn test_get_member_path() {
+        let path_list = [
+            ("a", 0, "a"),
+            ("./a/b", 0, "a/b"),
+            ("/a/b/", 0, "a/b"),
+            ("./", 0, ""),
+            ("x/a/b", 1, "a/b"),
+            ("x/", 1, ""),
+            ("x/a/b", 3, ""),
+        ];
+        for x in &path_list {
+            match super::get_member_path(std::path::Path::new(x.0), x.1) {
+                Ok(v) => assert_eq!(v, x.2, "{x:?}"),
+                Err(e) => panic!("{e} {x:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_archive() {
+        let mut b = tar::Builder::new(vec![]);
+        append_entry(&mut b, "x/", tar::EntryType::Directory, &[], "");
+        append_entry(&mut b, "x/a", tar::EntryType::Regular, b"abc", "");
+        append_entry(&mut b, "x/b", tar::EntryType::Link, &[], "x/a");
+        append_entry(&mut b, "x/c", tar::EntryType::Symlink, &[], "a");
+        append_entry(&mut b, "x/d", tar::EntryType::Fifo, &[], "");
+        let v = b.into_inner().unwrap();
+
+        let d = crate::util::TempDir::new("tar");
+        let f = d.join("x.tar");
+        std::fs::write(&f, v).unwrap();
+
+        let hash_algo = [crate::hash::SHA256.to_string()];
+        let l =
+            match super::read_archive(f.to_str().unwrap(), &super::ArchiveType::Tar, &hash_algo, 1)
+            {
+                Ok(v) => v,
+                Err(e) => panic!("{e}"),
+            };
+
+        assert_eq!(l.len(), 4);
+        assert_eq!(l[0].f, "a");
+        assert!(l[0].t.is_reg());
+        assert_eq!(l[0].written, 3);
+        assert_eq!(
+            crate::hash::get_hex_sum(&l[0].sum[0]),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(l[1].f, "b");
+        assert!(l[1].t.is_reg());
+        assert_eq!(l[1].sum, l[0].sum);
+        assert_eq!(l[0].nlink, 2);
+        assert_eq!(l[1].nlink, 2);
+        assert_eq!(l[2].f, "c");
+        assert!(l[2].t.is_symlink());
+        assert_eq!(l[3].f, "d");
+        assert!(l[3].t.is_unsupported());
+    }
+
+    #[test]
+    fn test_read_tar_dir() {
+        // same tree as directory and as tar in directory walk order
+        let d = crate::util::TempDir::new("tar_dir");
+        let x = d.join("x");
+        std::fs::create_dir_all(x.join("sub/sub")).unwrap();
+        std::fs::write(x.join("a"), b"abc").unwrap();
+        std::fs::write(x.join("sub/b"), b"xyz".repeat(100)).unwrap();
+        std::fs::write(x.join("sub/sub/c"), b"").unwrap();
+        std::os::unix::fs::symlink("a", x.join("l")).unwrap();
+
+        let mut b = tar::Builder::new(vec![]);
+        b.follow_symlinks(false);
+        for entry in walkdir::WalkDir::new(&x) {
+            let entry = entry.unwrap();
+            let f = entry.path().strip_prefix(&d).unwrap();
+            b.append_path_with_name(entry.path(), f).unwrap();
+        }
+        let f = d.join("x.tar");
+        std::fs::write(&f, b.into_inner().unwrap()).unwrap();
+
+        for sort in [false, true] {
+            let mut opt = crate::Opt {
+                archive: true,
+                strip_components: 1,
+                sort,
+                hashdeep_algo: vec![crate::hash::SHA256.to_string()],
+                ..Default::default()
+            };
+            opt.hash_algo = crate::hash::SHA256.to_string();
+            let mut ll = vec![];
+            let mut squash = vec![];
+            for f in [&x, &f] {
+                let f = f.to_str().unwrap();
+                opt.squash = false;
+                ll.push(crate::util::capture_record(|| {
+                    crate::dir::print_input(f, &mut crate::dir::Context::new(), &opt).unwrap();
+                }));
+                opt.squash = true;
+                squash.push(crate::dir::get_input_squash(f, &opt).unwrap());
+            }
+            assert_eq!(ll[0].lines().count(), 4, "{}", ll[0]);
+            assert_eq!(ll[0], ll[1], "{sort}");
+            assert!(!squash[0].is_empty());
+            assert_eq!(squash[0], squash[1], "{sort}");
+        }
+    }
+
+    #[test]
+    fn test_read_tar_hardlink() {
+        // hardlinks as link entries to first one walked as tar does
+        let d = crate::util::TempDir::new("tar_hardlink");
+        let x = d.join("x");
+        std::fs::create_dir_all(x.join("sub")).unwrap();
+        std::fs::write(x.join("a"), b"abc").unwrap();
+        std::fs::write(x.join("b"), b"abc").unwrap();
+        std::fs::hard_link(x.join("a"), x.join("sub/h")).unwrap();
+
+        let mut b = tar::Builder::new(vec![]);
+        let mut ino: std::collections::HashMap<u64, String> = std::collections::HashMap::new();
+        for entry in walkdir::WalkDir::new(&x) {
+            let entry = entry.unwrap();
+            let f = entry.path().strip_prefix(&d).unwrap();
+            let m = entry.metadata().unwrap();
+            if m.is_file() && std::os::unix::fs::MetadataExt::nlink(&m) > 1 {
+                let k = std::os::unix::fs::MetadataExt::ino(&m);
+                if let Some(v) = ino.get(&k) {
+                    let f = f.to_str().unwrap();
+                    append_entry(&mut b, f, tar::EntryType::Link, &[], v);
+                    continue;
+                }
+                ino.insert(k, f.to_str().unwrap().to_string());
+            }
+            b.append_path_with_name(entry.path(), f).unwrap();
+        }
+        let f = d.join("x.tar");
+        std::fs::write(&f, b.into_inner().unwrap()).unwrap();
+
+        let mut opt = crate::Opt {
+            archive: true,
+            strip_components: 1,
+            sort: true,
+            hashdeep_algo: vec![crate::hash::SHA256.to_string()],
+            ..Default::default()
+        };
+        opt.hash_algo = crate::hash::SHA256.to_string();
+        let mut squash = vec![];
+        for hardlink in [false, true] {
+            opt.hardlink = hardlink;
+            let mut ll = vec![];
+            let mut l = vec![];
+            for f in [&x, &f] {
+                let f = f.to_str().unwrap();
+                opt.squash = false;
+                ll.push(crate::util::capture_record(|| {
+                    crate::dir::print_input(f, &mut crate::dir::Context::new(), &opt).unwrap();
+                }));
+                opt.squash = true;
+                l.push(crate::dir::get_input_squash(f, &opt).unwrap());
+            }
+            let n = if hardlink { 2 } else { 0 };
+            assert_eq!(ll[0].matches("[hardlink]").count(), n, "{}", ll[0]);
+            assert_eq!(ll[0], ll[1], "{hardlink}");
+            assert_eq!(l[0], l[1], "{hardlink}");
+            squash.push(l.remove(0));
+        }
+        // link count is part of squash hash only if specified
+        assert_ne!(squash[0], squash[1]);
+    }
+
+    #[test]
+    fn test_read_zip() {
+        // same content with different compression and timestamp
+        let mut v = vec![];
+        for (i, method) in [
+            zip::CompressionMethod::Stored,
+            zip::CompressionMethod::Deflated,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let t = zip::DateTime::from_date_and_time(2000 + i as u16, 1, 1, 0, 0, 0).unwrap();
+            let mut b = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+            let o = zip::write::SimpleFileOptions::default()
+                .compression_method(*method)
+                .last_modified_time(t);
+            b.start_file("x/sub/a", o).unwrap();
+            std::io::Write::write_all(&mut b, b"abc".repeat(100).as_slice()).unwrap();
+            b.add_directory("x/d/", o).unwrap();
+            v.push(b.finish().unwrap().into_inner());
+        }
+        assert_ne!(v[0], v[1]);
+
+        let d = crate::util::TempDir::new("zip");
+        let mut opt = crate::Opt {
+            archive: true,
+            squash: true,
+            strip_components: 1,
+            hashdeep_algo: vec![crate::hash::SHA256.to_string()],
+            ..Default::default()
+        };
+        opt.hash_algo = crate::hash::SHA256.to_string();
+        let mut ll = vec![];
+        let mut squash = vec![];
+        for (i, b) in v.iter().enumerate() {
+            let f = d.join(format!("x{i}.zip"));
+            std::fs::write(&f, b).unwrap();
+            let f = f.to_str().unwrap();
+            match super::read_archive(f, &super::ArchiveType::Zip, &opt.hashdeep_algo, 1) {
+                Ok(v) => ll.push(v),
+                Err(e) => panic!("{e}"),
+            }
+            let b = crate::dir::get_input_squash(f, &opt).unwrap();
+            assert!(!b.is_empty());
+            squash.push(crate::hash::get_byte_hash(&b, &opt.hash_algo).unwrap().0);
+        }
+
+        for l in &ll {
+            assert_eq!(l.len(), 3);
+            assert_eq!(l[0].f, "sub");
+            assert!(l[0].t.is_dir());
+            assert_eq!(l[1].f, "sub/a");
+            assert!(l[1].t.is_reg());
+            assert_eq!(l[1].written, 300);
+            assert_eq!(l[2].f, "d");
+            assert!(l[2].t.is_dir());
+        }
+        assert_eq!(ll[0][1].sum, ll[1][1].sum);
+        let (b, _) =
+            crate::hash::get_byte_hash(b"abc".repeat(100).as_slice(), &opt.hash_algo).unwrap();
+        assert_eq!(ll[0][1].sum[0], b);
+        assert_eq!(squash[0], squash[1]);
+    }
+}
@@ -0,0 +1,183 @@
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+pub(crate) const READ: &str = "read";
+pub(crate) const MMAP: &str = "mmap";
+pub(crate) const DIRECT: &str = "direct";
+#[cfg(feature = "io_uring")]
+pub(crate) const IO_URING: &str = "io_uring";
+
+pub(crate) const DEFAULT_BUFFER_SIZE: usize = 65536;
+
+// smaller files are read, mapping them costs more than it saves
+const MMAP_THRESHOLD: u64 = 1 << 20;
+
+// O_DIRECT requires buffer address and size aligned to block size
+const DIRECT_ALIGN: usize = 4096;
+
+pub(crate) fn get_available_backend() -> Vec<&'static str> {
+    vec![
+        READ,
+        MMAP,
+        DIRECT,
+        #[cfg(feature = "io_uring")]
+        IO_URING,
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Backend {
+    pub(crate) name: String,
+    pub(crate) buffer_size: usize,
+    pub(crate) fadvise: bool,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self {
+            name: READ.to_string(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            fadvise: false,
+        }
+    }
+}
+
+impl Backend {
+    pub(crate) fn new(name: &str, buffer_size: usize, fadvise: bool) -> std::io::Result<Self> {
+        if !get_available_backend().contains(&name) || buffer_size == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            buffer_size,
+            fadvise,
+        })
+    }
+
+    pub(crate) fn is_mmap(&self, size: u64) -> bool {
+        self.name == MMAP && size >= MMAP_THRESHOLD
+    }
+
+    pub(crate) fn is_direct(&self) -> bool {
+        self.name == DIRECT
+    }
+
+    // small files are read ahead in batches, others are read as is
+    #[cfg(feature = "io_uring")]
+    pub(crate) fn is_io_uring(&self) -> bool {
+        self.name == IO_URING
+    }
+
+    // fall back to regular open if O_DIRECT unsupported, e.g. tmpfs
+    pub(crate) fn open_file(&self, f: &str) -> std::io::Result<std::fs::File> {
+        if self.is_direct() {
+            match std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_DIRECT)
+                .open(f)
+            {
+                Ok(v) => return Ok(v),
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        std::fs::File::open(f)
+    }
+
+    pub(crate) fn alloc_buffer(&self) -> Buffer {
+        Buffer::new(
+            self.buffer_size,
+            if self.is_direct() { DIRECT_ALIGN } else { 1 },
+        )
+    }
+
+    // advice is only a hint, hence errors are ignored
+    pub(crate) fn advise_start(&self, fp: &std::fs::File) {
+        if self.fadvise {
+            unsafe {
+                libc::posix_fadvise(fp.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+            }
+        }
+    }
+
+    // drop pages of this file so hashing a tree doesn't evict page cache
+    pub(crate) fn advise_end(&self, fp: &std::fs::File) {
+        if self.fadvise {
+            unsafe {
+                libc::posix_fadvise(fp.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Buffer {
+    v: Vec<u8>,
+    offset: usize,
+    size: usize,
+}
+
+impl Buffer {
+    fn new(size: usize, align: usize) -> Self {
+        let size = size.div_ceil(align) * align;
+        let v = vec![0; size + align - 1];
+        let offset = v.as_ptr().align_offset(align);
+        Self { v, offset, size }
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.v[self.offset..self.offset + self.size]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_new() {
+        for s in &super::get_available_backend() {
+            if let Err(e) = super::Backend::new(s, 4096, false) {
+                panic!("{e}");
+            }
+        }
+
+        let invalid_list = [("", 4096), ("xxx", 4096), ("READ", 4096), ("read", 0)];
+        for x in &invalid_list {
+            if let Ok(v) = super::Backend::new(x.0, x.1, false) {
+                panic!("{v:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_mmap() {
+        let b = super::Backend::new(super::MMAP, 4096, false).unwrap();
+        assert!(b.is_mmap(super::MMAP_THRESHOLD));
+        assert!(!b.is_mmap(super::MMAP_THRESHOLD - 1));
+        let b = super::Backend::default();
+        assert!(!b.is_mmap(super::MMAP_THRESHOLD));
+    }
+
+    #[test]
+    fn test_alloc_buffer() {
+        let buffer_list = [
+            (super::READ, 1, 1),
+            (super::READ, 65536, 65536),
+            (super::DIRECT, 1, 4096),
+            (super::DIRECT, 4097, 8192),
+        ];
+        for x in &buffer_list {
+            let b = super::Backend::new(x.0, x.1, false).unwrap();
+            let mut buf = b.alloc_buffer();
+            assert_eq!(buf.len(), x.2, "{x:?}");
+            let s = buf.as_mut_slice();
+            assert_eq!(s.len(), x.2, "{x:?}");
+            if b.is_direct() {
+                assert_eq!(s.as_ptr().align_offset(super::DIRECT_ALIGN), 0, "{x:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::hash;
+use std::os::unix::fs::MetadataExt;
+
+// dev, ino, size, mtime, ctime in nanoseconds
+pub(crate) type Key = (u64, u64, u64, i128, i128);
+
+// hash values of regular files by path, reused while file looks unchanged,
+// device content may change without its metadata changing
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    map: std::collections::HashMap<String, (Key, Vec<String>, hash::SparseSum)>,
+}
+
+impl Cache {
+    pub(crate) fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn get_sum(
+        &self,
+        f: &str,
+        m: &std::fs::Metadata,
+        hash_algo: &[String],
+    ) -> Option<&hash::SparseSum> {
+        if !m.is_file() {
+            return None;
+        }
+        match self.map.get(f) {
+            Some((k, a, v)) if *k == get_key(m) && a == hash_algo => Some(v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn insert_sum(
+        &mut self,
+        f: &str,
+        m: &std::fs::Metadata,
+        hash_algo: &[String],
+        sum: hash::SparseSum,
+    ) {
+        if !m.is_file() {
+            return;
+        }
+        self.map
+            .insert(f.to_string(), (get_key(m), hash_algo.to_vec(), sum));
+    }
+
+    // f and files under f if directory
+    pub(crate) fn remove(&mut self, f: &str) {
+        let prefix = format!("{f}/");
+        self.map.retain(|k, _| k != f && !k.starts_with(&prefix));
+    }
+
+    #[cfg(test)]
+    pub(crate) fn num_entry(&self) -> usize {
+        self.map.len()
+    }
+}
+
+pub(crate) fn get_key(m: &std::fs::Metadata) -> Key {
+    let mtime = i128::from(m.mtime()) * 1_000_000_000 + i128::from(m.mtime_nsec());
+    let ctime = i128::from(m.ctime()) * 1_000_000_000 + i128::from(m.ctime_nsec());
+    (m.dev(), m.ino(), m.size(), mtime, ctime)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_sum() {
+        let d = crate::util::TempDir::new("cache");
+        std::fs::create_dir_all(d.join("x")).unwrap();
+        let a = d.join("a");
+        let b = d.join("x/b");
+        std::fs::write(&a, "abc").unwrap();
+        std::fs::write(&b, "abc").unwrap();
+        let fa = a.to_str().unwrap();
+        let fb = b.to_str().unwrap();
+        let hash_algo = [crate::hash::SHA256.to_string()];
+        let sum = (vec![vec![1, 2, 3]], 3, 0);
+
+        let mut cac = super::Cache::new();
+        let m = std::fs::metadata(fa).unwrap();
+        assert!(cac.get_sum(fa, &m, &hash_algo).is_none());
+        cac.insert_sum(fa, &m, &hash_algo, sum.clone());
+        cac.insert_sum(fb, &std::fs::metadata(fb).unwrap(), &hash_algo, sum.clone());
+        assert_eq!(cac.get_sum(fa, &m, &hash_algo), Some(&sum));
+        assert_eq!(cac.num_entry(), 2);
+
+        // other algorithms or path
+        assert!(cac
+            .get_sum(fa, &m, &[crate::hash::MD5.to_string()])
+            .is_none());
+        assert!(cac.get_sum(fb, &m, &hash_algo).is_none());
+
+        // modified
+        std::fs::write(&a, "abcd").unwrap();
+        let m = std::fs::metadata(fa).unwrap();
+        assert!(cac.get_sum(fa, &m, &hash_algo).is_none());
+
+        // removed with directory
+        cac.remove(d.join("x").to_str().unwrap());
+        assert_eq!(cac.num_entry(), 1);
+    }
+}
@@ -0,0 +1,397 @@
+use crate::backend;
+use crate::hash;
+use crate::util;
+
+pub(crate) const CHUNK_LABEL: &str = "chunk";
+pub(crate) const MERKLE_LABEL: &str = "merkle";
+
+// e.g. "[chunk 1048576 0-1048575]" of chunk size and range,
+// both ends inclusive
+pub(crate) fn get_chunk_label(chunk_size: u64, start: u64, end: u64) -> String {
+    format!("[{CHUNK_LABEL} {chunk_size} {start}-{end}]")
+}
+
+// e.g. "[merkle 1048576]", root depends on chunk size
+pub(crate) fn get_merkle_label(chunk_size: u64) -> String {
+    format!("[{MERKLE_LABEL} {chunk_size}]")
+}
+
+// byte range and digest of chunks in order, last chunk may be short,
+// none if empty file
+pub(crate) type ChunkSum = (u64, u64, Vec<u8>);
+
+// whole file digest, written bytes and chunk digests in one read
+pub(crate) fn get_chunk_hash(
+    f: &str,
+    hash_algo: &str,
+    chunk_size: u64,
+    bk: &backend::Backend,
+) -> std::io::Result<(Vec<u8>, u64, Vec<ChunkSum>)> {
+    assert!(chunk_size > 0);
+    // O_DIRECT is not used as chunk boundaries may be unaligned
+    let mut fp = std::fs::File::open(f)?;
+    bk.advise_start(&fp);
+    let ret = get_chunk_hash_impl(&mut fp, hash_algo, chunk_size, bk.buffer_size);
+    bk.advise_end(&fp);
+    ret
+}
+
+fn get_chunk_hash_impl(
+    r: &mut impl std::io::Read,
+    hash_algo: &str,
+    chunk_size: u64,
+    buffer_size: usize,
+) -> std::io::Result<(Vec<u8>, u64, Vec<ChunkSum>)> {
+    let mut whole = hash::new_hash(hash_algo)?;
+    let mut buf = vec![0; buffer_size];
+    let mut l = vec![];
+    let mut written = 0;
+    loop {
+        let mut h = hash::new_hash(hash_algo)?;
+        let mut n = 0;
+        while n < chunk_size {
+            let size = usize::try_from(chunk_size - n).map_or(buf.len(), |v| v.min(buf.len()));
+            let ret = r.read(&mut buf[..size])?;
+            if ret == 0 {
+                break;
+            }
+            whole.update(&buf[..ret]);
+            h.update(&buf[..ret]);
+            n += u64::try_from(ret).unwrap();
+        }
+        if n == 0 {
+            break;
+        }
+        l.push((written, written + n - 1, h.finalize()));
+        written += n;
+        if n < chunk_size {
+            break;
+        }
+    }
+    Ok((whole.finalize(), written, l))
+}
+
+// tree of RFC 6962 over chunk digests, leaves hashed with 0x00 prefix and
+// nodes with 0x01 prefix, odd node promoted, digest of empty input if no chunk
+pub(crate) fn get_merkle_root(l: &[Vec<u8>], hash_algo: &str) -> std::io::Result<Vec<u8>> {
+    if l.is_empty() {
+        return Ok(hash::get_byte_hash(&[], hash_algo)?.0);
+    }
+    let mut v = vec![];
+    for x in l {
+        v.push(hash::get_byte_hash(&[&[0x00], &x[..]].concat(), hash_algo)?.0);
+    }
+    let mut l = v;
+    while l.len() > 1 {
+        let mut next = vec![];
+        for x in l.chunks(2) {
+            if x.len() == 1 {
+                next.push(x[0].clone());
+                continue;
+            }
+            let mut v = vec![0x01];
+            v.extend(&x[0]);
+            v.extend(&x[1]);
+            next.push(hash::get_byte_hash(&v, hash_algo)?.0);
+        }
+        l = next;
+    }
+    Ok(l.remove(0))
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Chunk {
+    known: std::collections::HashMap<String, Vec<(u64, u64, String)>>,
+    seen: std::collections::HashSet<String>,
+    passed: bool,
+}
+
+impl Chunk {
+    // chunk records printed with same --chunk_size, other records ignored,
+    // NUL terminated if null
+    pub(crate) fn new(f: &str, chunk_size: u64, swap: bool, null: bool) -> std::io::Result<Self> {
+        let mut chk = Self {
+            passed: true,
+            ..Default::default()
+        };
+        let s = std::fs::read_to_string(f)?;
+        let lines: Box<dyn Iterator<Item = &str>> = if null {
+            Box::new(s.split('\0'))
+        } else {
+            Box::new(s.lines())
+        };
+        for s in lines {
+            if let Some((realf, size, start, end, hex_sum)) = parse_chunk_record(s, swap) {
+                if size != chunk_size {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("chunk size {size} differs from {chunk_size}"),
+                    ));
+                }
+                chk.known
+                    .entry(realf)
+                    .or_default()
+                    .push((start, end, hex_sum));
+            }
+        }
+        if chk.known.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "no chunk record",
+            ));
+        }
+        Ok(chk)
+    }
+
+    pub(crate) fn is_passed(&self) -> bool {
+        self.passed
+    }
+
+    // differing byte ranges with adjacent ones merged, those in either
+    // file only included, whole file if unknown
+    pub(crate) fn verify_chunk(
+        &mut self,
+        realf: &str,
+        l: &[(u64, u64, String)],
+    ) -> Vec<(u64, u64)> {
+        self.seen.insert(realf.to_string());
+        let empty = vec![];
+        let known = self.known.get(realf).unwrap_or(&empty);
+        let set: std::collections::HashSet<_> = known.iter().collect();
+        let size = l.last().map_or(0, |x| x.1 + 1);
+
+        let mut v: Vec<(u64, u64)> = vec![];
+        let mut append = |start: u64, end: u64| match v.last_mut() {
+            Some(x) if x.1 + 1 >= start => x.1 = x.1.max(end),
+            _ => v.push((start, end)),
+        };
+        for y in l {
+            if !set.contains(y) {
+                append(y.0, y.1);
+            }
+        }
+        // truncated since
+        let mut l: Vec<_> = known.iter().filter(|x| x.1 >= size).collect();
+        l.sort_unstable();
+        for x in l {
+            append(x.0.max(size), x.1);
+        }
+
+        if !v.is_empty() {
+            self.passed = false;
+        }
+        v
+    }
+
+    // known files never verified, e.g. removed since, differ as a whole
+    pub(crate) fn get_unseen(&mut self) -> Vec<(String, u64, u64)> {
+        let mut v = vec![];
+        for (realf, l) in &self.known {
+            if self.seen.contains(realf) {
+                continue;
+            }
+            let start = l.iter().map(|x| x.0).min().unwrap();
+            let end = l.iter().map(|x| x.1).max().unwrap();
+            v.push((realf.clone(), start, end));
+        }
+        v.sort_unstable();
+        if !v.is_empty() {
+            self.passed = false;
+        }
+        v
+    }
+
+    pub(crate) fn print_unseen(&mut self, null: bool) {
+        for (realf, start, end) in self.get_unseen() {
+            util::print_record(&format!("{realf}: {start}-{end}"), null);
+        }
+    }
+}
+
+// "<hex>  <path>[chunk <size> <start>-<end>]", or path first if swapped
+fn parse_chunk_record(s: &str, swap: bool) -> Option<(String, u64, u64, u64, String)> {
+    let s = s.strip_suffix(']')?;
+    let (s, label) = s.rsplit_once(&format!("[{CHUNK_LABEL} "))?;
+    let (size, range) = label.split_once(' ')?;
+    let (start, end) = range.split_once('-')?;
+    let (size, start, end) = (
+        size.parse::<u64>().ok()?,
+        start.parse::<u64>().ok()?,
+        end.parse::<u64>().ok()?,
+    );
+    // range within a chunk at chunk boundary
+    if size == 0 || start > end || start % size != 0 || end - start >= size {
+        return None;
+    }
+    let (realf, hex_sum) = if swap {
+        s.rsplit_once("  ")?
+    } else {
+        let (h, f) = s.split_once("  ")?;
+        (f, h)
+    };
+    if !util::is_valid_hexsum(hex_sum).1 {
+        return None;
+    }
+    Some((realf.to_string(), size, start, end, hex_sum.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_chunk_hash() {
+        let d = crate::util::TempDir::new("chunk");
+        let f = d.join("x");
+        let b: Vec<u8> = (0..10000u32).map(|x| (x % 251) as u8).collect();
+        std::fs::write(&f, &b).unwrap();
+        let bk = crate::backend::Backend::default();
+        let algo = crate::hash::SHA256;
+
+        let whole = crate::hash::get_byte_hash(&b, algo).unwrap().0;
+        for chunk_size in [1000, 4096, 10000, 20000] {
+            let (sum, written, l) =
+                super::get_chunk_hash(f.to_str().unwrap(), algo, chunk_size, &bk).unwrap();
+            assert_eq!(sum, whole);
+            assert_eq!(written, 10000);
+            let v: Vec<_> = b.chunks(chunk_size as usize).collect();
+            assert_eq!(l.len(), v.len(), "{chunk_size}");
+            for (i, (start, end, sum)) in l.iter().enumerate() {
+                assert_eq!(*start, i as u64 * chunk_size);
+                assert_eq!(end - start + 1, v[i].len() as u64);
+                assert_eq!(*sum, crate::hash::get_byte_hash(v[i], algo).unwrap().0);
+            }
+        }
+        std::fs::write(&f, b"").unwrap();
+        let (sum, written, l) =
+            super::get_chunk_hash(f.to_str().unwrap(), algo, 1000, &bk).unwrap();
+        assert_eq!(sum, crate::hash::get_byte_hash(&[], algo).unwrap().0);
+        assert_eq!(written, 0);
+        assert!(l.is_empty());
+    }
+
+    #[test]
+    fn test_get_merkle_root() {
+        let algo = crate::hash::SHA256;
+        let h = |v: &[u8]| crate::hash::get_byte_hash(v, algo).unwrap().0;
+        let node = |a: &[u8], b: &[u8]| h(&[&[0x01], a, b].concat());
+        let l: Vec<Vec<u8>> = (0..5u8).map(|x| h(&[x])).collect();
+        let leaf: Vec<Vec<u8>> = l.iter().map(|x| h(&[&[0x00], &x[..]].concat())).collect();
+
+        assert_eq!(super::get_merkle_root(&[], algo).unwrap(), h(&[]));
+        assert_eq!(super::get_merkle_root(&l[..1], algo).unwrap(), leaf[0]);
+        assert_eq!(
+            super::get_merkle_root(&l[..3], algo).unwrap(),
+            node(&node(&leaf[0], &leaf[1]), &leaf[2])
+        );
+        assert_eq!(
+            super::get_merkle_root(&l, algo).unwrap(),
+            node(
+                &node(&node(&leaf[0], &leaf[1]), &node(&leaf[2], &leaf[3])),
+                &leaf[4]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_chunk_record() {
+        let h = "0".repeat(64);
+        assert_eq!(
+            super::parse_chunk_record(&format!("{h}  a b[chunk 10 0-9]"), false),
+            Some(("a b".to_string(), 10, 0, 9, h.clone()))
+        );
+        assert_eq!(
+            super::parse_chunk_record(&format!("a  b  {h}[chunk 10 10-14]"), true),
+            Some(("a  b".to_string(), 10, 10, 14, h.clone()))
+        );
+        let invalid_list = [
+            format!("{h}  a"),
+            format!("{h}  a[chunk 0-9]"),
+            format!("{h}  a[chunk 10 9-0]"),
+            format!("{h}  a[chunk 10 0-x]"),
+            format!("{h}  a[chunk 0 0-9]"),
+            format!("{h}  a[chunk 10 5-9]"),
+            format!("{h}  a[chunk 10 0-10]"),
+            format!("{h}  a[merkle 10]"),
+            "xxx  a[chunk 10 0-9]".to_string(),
+        ];
+        for s in &invalid_list {
+            assert!(super::parse_chunk_record(s, false).is_none(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_verify_chunk() {
+        let h = |c: char| c.to_string().repeat(64);
+        let mut chk = super::Chunk {
+            passed: true,
+            ..Default::default()
+        };
+        chk.known.insert(
+            "a".to_string(),
+            vec![
+                (0, 9, h('0')),
+                (10, 19, h('1')),
+                (20, 29, h('2')),
+                (30, 39, h('3')),
+                (40, 44, h('4')),
+            ],
+        );
+
+        // chunk of 10 bytes from digest chars
+        let get = |s: &str, size: u64| -> Vec<(u64, u64, String)> {
+            s.chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    let start = i as u64 * 10;
+                    (start, size.min(start + 10) - 1, h(c))
+                })
+                .collect()
+        };
+
+        assert!(chk.verify_chunk("a", &get("01234", 45)).is_empty());
+        assert!(chk.is_passed());
+
+        // adjacent ranges merged
+        assert_eq!(
+            chk.verify_chunk("a", &get("0xx3x", 45)),
+            [(10, 29), (40, 44)]
+        );
+        assert!(!chk.is_passed());
+
+        // grown and truncated
+        assert_eq!(chk.verify_chunk("a", &get("0123x", 50)), [(40, 49)]);
+        assert_eq!(chk.verify_chunk("a", &get("012", 25)), [(20, 44)]);
+        assert!(chk.verify_chunk("a", &[]).len() == 1);
+
+        // unknown file
+        assert_eq!(chk.verify_chunk("b", &get("0", 5)), [(0, 4)]);
+
+        // known file never verified
+        chk.known
+            .insert("c".to_string(), vec![(0, 9, h('0')), (10, 14, h('1'))]);
+        assert_eq!(chk.get_unseen(), [("c".to_string(), 0, 14)]);
+        chk.passed = true;
+        chk.verify_chunk("c", &get("01", 15));
+        assert!(chk.get_unseen().is_empty());
+        assert!(chk.is_passed());
+    }
+
+    #[test]
+    fn test_new() {
+        let d = crate::util::TempDir::new("chunk-new");
+        let f = d.join("x");
+        let h = "0".repeat(64);
+        for null in [false, true] {
+            let sep = if null { "\0" } else { "\n" };
+            let s = format!("{h}  a\nb[chunk 10 0-9]{sep}{h}  c[chunk 10 0-4]{sep}{h}  c{sep}");
+            std::fs::write(&f, s).unwrap();
+            let chk = super::Chunk::new(f.to_str().unwrap(), 10, false, null).unwrap();
+            assert_eq!(chk.known.len(), if null { 2 } else { 1 }, "{null}");
+            assert!(chk.known.contains_key("c"));
+
+            // printed with other chunk size
+            let e = super::Chunk::new(f.to_str().unwrap(), 20, false, null).unwrap_err();
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(e.to_string(), "chunk size 10 differs from 20");
+        }
+    }
+}
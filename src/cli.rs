@@ -0,0 +1,916 @@
+use crate::config;
+use crate::filter;
+use crate::hash;
+
+pub(crate) const CMD_HASH: &str = "hash";
+pub(crate) const CMD_CHECK: &str = "check";
+pub(crate) const CMD_DIFF: &str = "diff";
+pub(crate) const CMD_SQUASH: &str = "squash";
+pub(crate) const CMD_DUPES: &str = "dupes";
+pub(crate) const CMD_SERVE: &str = "serve";
+pub(crate) const CMD_CLIENT: &str = "client";
+pub(crate) const CMD_COMPLETION: &str = "completion";
+
+// commands which walk input paths
+const WALK: &[&str] = &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES];
+
+// command, operands, description
+const COMMAND: [(&str, &str, &str); 8] = [
+    (
+        CMD_HASH,
+        "<paths>",
+        "Print message digest of files under paths",
+    ),
+    (
+        CMD_CHECK,
+        "<known file> <paths>",
+        "Audit paths against hashdeep format file of known hashes",
+    ),
+    (
+        CMD_DIFF,
+        "<path1> <path2>",
+        "Print files changed, moved, added or removed in path2 relative to path1",
+    ),
+    (
+        CMD_SQUASH,
+        "<paths>",
+        "Print squashed message digest of paths",
+    ),
+    (CMD_DUPES, "<paths>", "Print duplicate files under paths"),
+    (CMD_SERVE, "", "Serve hash requests over Unix domain socket"),
+    (
+        CMD_CLIENT,
+        "<op> <path>",
+        "Send hash request to server over Unix domain socket",
+    ),
+    (
+        CMD_COMPLETION,
+        "<shell>",
+        "Print completion script for bash, zsh or fish",
+    ),
+];
+
+pub(crate) const SHELL_BASH: &str = "bash";
+pub(crate) const SHELL_ZSH: &str = "zsh";
+pub(crate) const SHELL_FISH: &str = "fish";
+
+pub(crate) fn get_available_shell() -> [&'static str; 3] {
+    [SHELL_BASH, SHELL_ZSH, SHELL_FISH]
+}
+
+// option accepted by listed commands, and always without command
+#[derive(Debug)]
+struct Arg {
+    short: &'static str,
+    long: &'static str,
+    hint: &'static str, // empty if flag
+    desc: String,
+    cmd: &'static [&'static str],
+}
+
+#[allow(clippy::too_many_lines)]
+fn get_arg_list() -> Vec<Arg> {
+    let arg = |short, long, hint, desc: &str, cmd| Arg {
+        short,
+        long,
+        hint,
+        desc: desc.to_string(),
+        cmd,
+    };
+    vec![
+        arg(
+            "a",
+            "hash_algo",
+            "<string>",
+            "Hash algorithm to use (default \"sha256\")",
+            &[CMD_HASH, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "hash_verify",
+            "<string>",
+            "Message digest to verify in hex string",
+            &[CMD_HASH],
+        ),
+        arg(
+            "",
+            "hash_only",
+            "",
+            "Do not print file paths",
+            &[CMD_HASH, CMD_SQUASH, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "ignore_dot",
+            "",
+            "Ignore entries start with .",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "ignore_dot_dir",
+            "",
+            "Ignore directories start with .",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "ignore_dot_file",
+            "",
+            "Ignore files start with .",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "ignore_symlink",
+            "",
+            "Ignore symbolic links",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "L",
+            "follow_symlink",
+            "",
+            "Follow symbolic links unless directory",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "abs",
+            "",
+            "Print file paths in absolute path",
+            &[CMD_HASH, CMD_CHECK, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "swap",
+            "",
+            "Print file path first in each line",
+            &[CMD_HASH, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "s",
+            "sort",
+            "",
+            "Print sorted file paths",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_DUPES, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "squash",
+            "",
+            "Print squashed message digest instead of per file",
+            &[],
+        ),
+        arg(
+            "",
+            "squash_depth",
+            "<num>",
+            "Also print squashed message digest per subdirectory at this depth",
+            &[CMD_SQUASH, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "hashdeep",
+            "",
+            "Print in hashdeep format with comma separated --hash_algo",
+            &[CMD_HASH, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "audit",
+            "<path>",
+            "Audit against hashdeep format file of known hashes",
+            &[],
+        ),
+        arg(
+            "",
+            "archive",
+            "",
+            "Hash members of tar or zip archive inputs as if directory",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "strip_components",
+            "<num>",
+            "Strip leading components from archive member paths",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "duplicates",
+            "",
+            "Print duplicate files instead of per file",
+            &[],
+        ),
+        arg(
+            "",
+            "duplicates_partial",
+            "",
+            "Compare first and last blocks before full hash with --duplicates",
+            &[CMD_DUPES],
+        ),
+        arg(
+            "",
+            "hardlink",
+            "",
+            "Mark hardlinks and include link count in squash",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH, CMD_CLIENT],
+        ),
+        arg(
+            "d",
+            "max_depth",
+            "<num>",
+            "Walk at most this many levels below input prefix",
+            WALK,
+        ),
+        arg(
+            "",
+            "min_depth",
+            "<num>",
+            "Walk entries at least this many levels below input prefix",
+            WALK,
+        ),
+        arg(
+            "",
+            "io_backend",
+            "<string>",
+            "I/O backend to read files with (default \"read\")",
+            WALK,
+        ),
+        arg(
+            "",
+            "buffer_size",
+            "<num>",
+            "Read buffer size in bytes (default 65536)",
+            WALK,
+        ),
+        arg(
+            "",
+            "fadvise",
+            "",
+            "Advise sequential read and drop page cache after read",
+            WALK,
+        ),
+        arg(
+            "",
+            "min_size",
+            "<size>",
+            "Hash regular files of at least this size with optional K, M or G suffix",
+            WALK,
+        ),
+        arg(
+            "",
+            "max_size",
+            "<size>",
+            "Hash regular files of at most this size with optional K, M or G suffix",
+            WALK,
+        ),
+        arg(
+            "",
+            "newer_than",
+            "<time>",
+            "Hash files modified after unix time, YYYY-MM-DD[THH:MM:SS] in UTC, or mtime of file",
+            WALK,
+        ),
+        arg(
+            "",
+            "older_than",
+            "<time>",
+            "Hash files modified before unix time, YYYY-MM-DD[THH:MM:SS] in UTC, or mtime of file",
+            WALK,
+        ),
+        arg(
+            "",
+            "type",
+            "<list>",
+            "Hash comma separated file types of f, d, l, b, c, p and s only",
+            WALK,
+        ),
+        arg(
+            "",
+            "char_device",
+            "",
+            "Hash character devices, which are skipped as they may never end",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "device_offset",
+            "<size>",
+            "Hash devices from this byte offset with optional K, M or G suffix",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "device_length",
+            "<size>",
+            "Hash devices up to this many bytes with optional K, M or G suffix, block devices up to their size",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "device_number",
+            "",
+            "Hash device type, major and minor numbers and permissions instead of content",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "special",
+            "",
+            "Hash FIFOs and sockets by type and permissions, which are otherwise skipped to keep output same as dirhash",
+            &[CMD_HASH, CMD_CHECK, CMD_DIFF, CMD_SQUASH],
+        ),
+        arg(
+            "",
+            "chunk_size",
+            "<size>",
+            "Also print digest of each chunk of regular files of this many bytes with optional K, M or G suffix",
+            &[CMD_HASH],
+        ),
+        arg(
+            "",
+            "chunk_merkle",
+            "",
+            "Print Merkle root of --chunk_size chunk digests instead of each digest",
+            &[CMD_HASH],
+        ),
+        arg(
+            "",
+            "chunk_verify",
+            "<path>",
+            "Print byte ranges of regular files differing from chunk digests in file printed with same --chunk_size",
+            &[CMD_HASH],
+        ),
+        arg(
+            "w",
+            "watch",
+            "",
+            "Re-hash changed files after walk until interrupted, or print updated squash hash if squashed",
+            &[CMD_HASH, CMD_SQUASH],
+        ),
+        arg(
+            "p",
+            "progress",
+            "",
+            "Print progress to stderr if terminal",
+            WALK,
+        ),
+        arg(
+            "",
+            "progress_scan",
+            "",
+            "Total file sizes before walk to estimate remaining time with --progress",
+            WALK,
+        ),
+        arg(
+            "T",
+            "files_from",
+            "<path>",
+            "Read input paths from file, or stdin if -, squashed together if squashed",
+            &[CMD_HASH, CMD_CHECK, CMD_SQUASH, CMD_DUPES],
+        ),
+        arg(
+            "",
+            "null_input",
+            "",
+            "Read --files_from input paths terminated with NUL instead of newline",
+            &[CMD_HASH, CMD_CHECK, CMD_SQUASH, CMD_DUPES],
+        ),
+        arg(
+            "z",
+            "null",
+            "",
+            "Terminate output records with NUL instead of newline",
+            WALK,
+        ),
+        arg(
+            "o",
+            "output_file",
+            "<path>",
+            "Write records to file via atomic rename, with its sha256 digest in <path>.sha256",
+            WALK,
+        ),
+        arg(
+            "",
+            "sign",
+            "<path>",
+            "Sign --output_file with signify format Ed25519 secret key into <path>.sig",
+            WALK,
+        ),
+        arg(
+            "",
+            "verify_signature",
+            "<path>",
+            "Verify known file signature in <path>.sig with signify format Ed25519 public key",
+            &[CMD_CHECK],
+        ),
+        arg(
+            "",
+            "hmac_key_file",
+            "<path>",
+            &format!(
+                "Key file for HMAC, or keyed BLAKE3 with {}-byte key, overriding hex encoded key in {}",
+                hash::BLAKE3_KEY_SIZE,
+                hash::HMAC_KEY_ENV
+            ),
+            WALK,
+        ),
+        arg(
+            "",
+            "socket",
+            "<path>",
+            "Unix domain socket path of server",
+            &[CMD_SERVE, CMD_CLIENT],
+        ),
+        arg(
+            "",
+            "manifest",
+            "<path>",
+            "Hashdeep format file of known hashes to verify against",
+            &[CMD_CLIENT],
+        ),
+        arg("", "verbose", "", "Enable verbose print", WALK),
+        arg("", "debug", "", "Enable debug print", WALK),
+        arg("v", "version", "", "Print version and exit", &[]),
+        arg(
+            "h",
+            "help",
+            "",
+            "Print usage and exit",
+            &[
+                CMD_HASH,
+                CMD_CHECK,
+                CMD_DIFF,
+                CMD_SQUASH,
+                CMD_DUPES,
+                CMD_SERVE,
+                CMD_CLIENT,
+                CMD_COMPLETION,
+            ],
+        ),
+    ]
+}
+
+pub(crate) fn is_command(s: &str) -> bool {
+    COMMAND.iter().any(|x| x.0 == s)
+}
+
+// options of command, or deprecated options without command if none
+fn get_command_arg_list(cmd: Option<&str>) -> Vec<Arg> {
+    let mut l = get_arg_list();
+    if let Some(cmd) = cmd {
+        l.retain(|x| x.cmd.contains(&cmd));
+    } else {
+        // not server and client specific
+        l.retain(|x| x.cmd.is_empty() || x.cmd.iter().any(|c| WALK.contains(c)));
+    }
+    l
+}
+
+pub(crate) fn get_options(cmd: Option<&str>) -> getopts::Options {
+    let mut opts = getopts::Options::new();
+    for x in get_command_arg_list(cmd) {
+        if x.hint.is_empty() {
+            opts.optflag(x.short, x.long, &x.desc);
+        } else {
+            opts.optopt(x.short, x.long, &x.desc, x.hint);
+        }
+    }
+    opts
+}
+
+pub(crate) fn get_usage(progname: &str, cmd: Option<&str>) -> String {
+    let opts = get_options(cmd);
+    let Some(cmd) = cmd else {
+        let mut s = format!("usage: {progname} <command> [<options>] <operands>\n\nCommands:\n");
+        for (x, _, desc) in &COMMAND {
+            s.push_str(&format!("    {x:<12}{desc}\n"));
+        }
+        s.push_str(&format!(
+            "\nRun \"{progname} <command> -h\" for options of each command.\n\n\
+             Options not specified default to those in $XDG_CONFIG_HOME/{}/{},\n\
+             {} in first input directory, then {}<OPTION> environment variables.\n\
+             Flags set there are turned off with {}<flag>, e.g. {}sort.\n\n\
+             Options without command are deprecated, and with paths behave as hash\n\
+             unless --squash, --audit or --duplicates is specified.\n\n\
+             usage: {progname} [<options>] <paths>",
+            config::CONFIG_DIR,
+            config::CONFIG_FILE,
+            config::TREE_CONFIG_FILE,
+            config::ENV_PREFIX,
+            NEGATE_PREFIX,
+            NEGATE_PREFIX
+        ));
+        return opts.usage(&s);
+    };
+    let operand = COMMAND.iter().find(|x| x.0 == cmd).map_or("", |x| x.1);
+    if cmd == CMD_SERVE {
+        opts.usage(&format!("usage: {progname} {cmd} --socket <path>"))
+    } else {
+        opts.usage(&format!("usage: {progname} {cmd} [<options>] {operand}"))
+    }
+}
+
+// true if flag, none if no such option or not configurable
+pub(crate) fn get_arg_flag(long: &str) -> Option<bool> {
+    if long == "help" || long == "version" {
+        return None;
+    }
+    get_arg_list()
+        .iter()
+        .find(|x| x.long == long)
+        .map(|x| x.hint.is_empty())
+}
+
+pub(crate) fn is_command_arg(cmd: Option<&str>, long: &str) -> bool {
+    get_command_arg_list(cmd).iter().any(|x| x.long == long)
+}
+
+pub(crate) const NEGATE_PREFIX: &str = "--no_";
+
+// getopts panics on options not defined for command,
+// options not specified fall back to config
+#[derive(Debug)]
+pub(crate) struct Matches {
+    m: getopts::Matches,
+    cfg: Option<config::Config>,
+    negated: Vec<String>, // flags turned off by --no_<flag>
+}
+
+impl Matches {
+    pub(crate) fn parse(cmd: Option<&str>, args: &[String]) -> Result<Self, getopts::Fail> {
+        // e.g. --no_sort for sort set by config, not with --sort
+        let flag_list: Vec<&str> = get_command_arg_list(cmd)
+            .iter()
+            .filter(|x| x.hint.is_empty())
+            .map(|x| x.long)
+            .collect();
+        let mut l = vec![];
+        let mut negated = vec![];
+        for (i, x) in args.iter().enumerate() {
+            if x == "--" {
+                l.extend_from_slice(&args[i..]);
+                break;
+            }
+            match x.strip_prefix(NEGATE_PREFIX) {
+                Some(k) if flag_list.contains(&k) => negated.push(k.to_string()),
+                _ => l.push(x.clone()),
+            }
+        }
+        let m = get_options(cmd).parse(l)?;
+        if let Some(k) = negated.iter().find(|k| m.opt_present(k)) {
+            return Err(getopts::Fail::OptionDuplicated(k.clone()));
+        }
+        Ok(Self {
+            m,
+            cfg: None,
+            negated,
+        })
+    }
+
+    pub(crate) fn set_config(&mut self, cfg: config::Config) {
+        self.cfg = Some(cfg);
+    }
+
+    pub(crate) fn opt_present(&self, k: &str) -> bool {
+        if self.negated.iter().any(|x| x == k) {
+            return false;
+        }
+        self.m.opts_present(&[k.to_string()])
+            || self
+                .cfg
+                .as_ref()
+                .and_then(|v| v.get_flag(k))
+                .unwrap_or(false)
+    }
+
+    pub(crate) fn opt_str(&self, k: &str) -> Option<String> {
+        if self.m.opts_present(&[k.to_string()]) {
+            self.m.opt_str(k)
+        } else {
+            self.cfg
+                .as_ref()
+                .and_then(|v| v.get_str(k))
+                .map(str::to_string)
+        }
+    }
+
+    pub(crate) fn free(&self) -> &[String] {
+        &self.m.free
+    }
+}
+
+// name of completion function, e.g. _dirhash_rs
+fn get_function_name(progname: &str) -> String {
+    format!(
+        "_{}",
+        progname.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    )
+}
+
+fn get_option_word_list(l: &[Arg]) -> Vec<String> {
+    let mut v = vec![];
+    for x in l {
+        if !x.short.is_empty() {
+            v.push(format!("-{}", x.short));
+        }
+        v.push(format!("--{}", x.long));
+    }
+    v
+}
+
+// option value choices if any
+fn get_value_list(long: &str) -> Vec<String> {
+    match long {
+        "hash_algo" => hash::get_available_hash_algo()
+            .iter()
+            .map(|x| (*x).to_string())
+            .collect(),
+        "type" => filter::get_available_type()
+            .iter()
+            .map(|x| (*x).to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+pub(crate) fn get_completion(progname: &str, shell: &str) -> std::io::Result<String> {
+    match shell {
+        SHELL_BASH => Ok(get_bash_completion(progname)),
+        SHELL_ZSH => Ok(get_zsh_completion(progname)),
+        SHELL_FISH => Ok(get_fish_completion(progname)),
+        _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+    }
+}
+
+fn get_bash_completion(progname: &str) -> String {
+    let func = get_function_name(progname);
+    let cmd: Vec<&str> = COMMAND.iter().map(|x| x.0).collect();
+    let mut s = format!(
+        "{func}() {{\n\
+         \x20   local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n\
+         \x20   local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n\
+         \x20   local opts\n\
+         \x20   if [ \"$COMP_CWORD\" -eq 1 ]; then\n\
+         \x20       COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n\
+         \x20       return\n\
+         \x20   fi\n\
+         \x20   case \"$prev\" in\n",
+        cmd.join(" ")
+    );
+    for x in get_arg_list() {
+        let l = get_value_list(x.long);
+        if !l.is_empty() {
+            s.push_str(&format!(
+                "        --{})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return\n            ;;\n",
+                x.long,
+                l.join(" ")
+            ));
+        }
+    }
+    s.push_str("    esac\n    case \"${COMP_WORDS[1]}\" in\n");
+    for x in cmd {
+        let l = get_option_word_list(&get_command_arg_list(Some(x)));
+        s.push_str(&format!(
+            "        {x})\n            opts=\"{}\"\n            ;;\n",
+            l.join(" ")
+        ));
+    }
+    s.push_str(&format!(
+        "    esac\n\
+         \x20   if [ \"${{COMP_WORDS[1]}}\" = \"{CMD_COMPLETION}\" ]; then\n\
+         \x20       COMPREPLY=($(compgen -W \"{} $opts\" -- \"$cur\"))\n\
+         \x20   elif [[ \"$cur\" == -* ]]; then\n\
+         \x20       COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n\
+         \x20   else\n\
+         \x20       COMPREPLY=($(compgen -f -- \"$cur\"))\n\
+         \x20   fi\n\
+         }}\n\
+         complete -o filenames -F {func} {progname}\n",
+        get_available_shell().join(" ")
+    ));
+    s
+}
+
+// escape for single quoted _arguments spec
+fn get_zsh_string(s: &str) -> String {
+    s.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn get_zsh_completion(progname: &str) -> String {
+    let func = get_function_name(progname);
+    let mut s = format!(
+        "#compdef {progname}\n\n\
+         {func}() {{\n\
+         \x20   local -a commands\n\
+         \x20   commands=(\n"
+    );
+    for (x, _, desc) in &COMMAND {
+        s.push_str(&format!("        '{x}:{}'\n", get_zsh_string(desc)));
+    }
+    s.push_str(
+        "    )\n\
+         \x20   if (( CURRENT == 2 )); then\n\
+         \x20       _describe command commands\n\
+         \x20       return\n\
+         \x20   fi\n\
+         \x20   shift words\n\
+         \x20   (( CURRENT-- ))\n\
+         \x20   case $words[1] in\n",
+    );
+    for (x, _, _) in &COMMAND {
+        s.push_str(&format!("        {x})\n            _arguments \\\n"));
+        for a in get_command_arg_list(Some(x)) {
+            let desc = get_zsh_string(&a.desc);
+            let value = if a.hint.is_empty() {
+                String::new()
+            } else {
+                let l = get_value_list(a.long);
+                let action = if !l.is_empty() {
+                    format!("({})", l.join(" "))
+                } else if a.hint == "<path>" {
+                    "_files".to_string()
+                } else {
+                    " ".to_string()
+                };
+                format!(":{}:{action}", a.hint.trim_matches(['<', '>']))
+            };
+            if !a.short.is_empty() {
+                s.push_str(&format!(
+                    "                '-{}[{desc}]{value}' \\\n",
+                    a.short
+                ));
+            }
+            s.push_str(&format!(
+                "                '--{}[{desc}]{value}' \\\n",
+                a.long
+            ));
+        }
+        if *x == CMD_COMPLETION {
+            s.push_str(&format!(
+                "                ':shell:({})'\n",
+                get_available_shell().join(" ")
+            ));
+        } else {
+            s.push_str("                '*:path:_files'\n");
+        }
+        s.push_str("            ;;\n");
+    }
+    s.push_str(&format!("    esac\n}}\n\n{func} \"$@\"\n"));
+    s
+}
+
+// escape for single quoted fish string
+fn get_fish_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn get_fish_completion(progname: &str) -> String {
+    let mut s = String::new();
+    for (x, _, desc) in &COMMAND {
+        s.push_str(&format!(
+            "complete -c {progname} -n __fish_use_subcommand -f -a {x} -d '{}'\n",
+            get_fish_string(desc)
+        ));
+    }
+    for (x, _, _) in &COMMAND {
+        for a in get_command_arg_list(Some(x)) {
+            s.push_str(&format!(
+                "complete -c {progname} -n '__fish_seen_subcommand_from {x}'"
+            ));
+            if !a.short.is_empty() {
+                s.push_str(&format!(" -s {}", a.short));
+            }
+            s.push_str(&format!(" -l {}", a.long));
+            if !a.hint.is_empty() {
+                s.push_str(" -r");
+                let l = get_value_list(a.long);
+                if !l.is_empty() {
+                    s.push_str(&format!(" -f -a '{}'", l.join(" ")));
+                }
+            }
+            s.push_str(&format!(" -d '{}'\n", get_fish_string(&a.desc)));
+        }
+    }
+    s.push_str(&format!(
+        "complete -c {progname} -n '__fish_seen_subcommand_from {CMD_COMPLETION}' -f -a '{}'\n",
+        get_available_shell().join(" ")
+    ));
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_command_arg_list() {
+        // unique names and short names within each command
+        for (x, _, _) in &super::COMMAND {
+            let l = super::get_command_arg_list(Some(x));
+            let w = super::get_option_word_list(&l);
+            let mut v = w.clone();
+            v.sort();
+            v.dedup();
+            assert_eq!(v.len(), w.len(), "{x}");
+        }
+
+        // deprecated options are the same as before commands
+        let l = super::get_command_arg_list(None);
+        for x in ["squash", "audit", "duplicates", "version", "hash_algo"] {
+            assert!(l.iter().any(|a| a.long == x), "{x}");
+        }
+        for x in ["socket", "manifest"] {
+            assert!(!l.iter().any(|a| a.long == x), "{x}");
+        }
+
+        // client options map to request fields
+        let l = super::get_command_arg_list(Some(super::CMD_CLIENT));
+        for x in &l {
+            assert!(
+                crate::serve::REQUEST_BOOL.contains(&x.long)
+                    || [
+                        crate::serve::REQUEST_HASH_ALGO,
+                        crate::serve::REQUEST_SQUASH_DEPTH,
+                        "socket",
+                        "manifest",
+                        "help"
+                    ]
+                    .contains(&x.long),
+                "{}",
+                x.long
+            );
+        }
+        for x in crate::serve::REQUEST_BOOL {
+            assert!(l.iter().any(|a| a.long == x), "{x}");
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let args: Vec<String> = ["-a", "md5", "-sz", "--null_input", "x", "y"]
+            .iter()
+            .map(|x| (*x).to_string())
+            .collect();
+        let m = super::Matches::parse(Some(super::CMD_HASH), &args).unwrap();
+        assert_eq!(m.opt_str("hash_algo"), Some("md5".to_string()));
+        assert!(m.opt_present("sort"));
+        assert!(m.opt_present("null"));
+        assert!(m.opt_present("null_input"));
+        assert!(!m.opt_present("abs"));
+        assert_eq!(m.free(), ["x", "y"]);
+
+        // not defined for command
+        assert!(!m.opt_present("squash"));
+        assert!(m.opt_str("audit").is_none());
+        let args = vec!["--squash".to_string(), "x".to_string()];
+        assert!(super::Matches::parse(Some(super::CMD_HASH), &args).is_err());
+        assert!(super::Matches::parse(None, &args).is_ok());
+
+        // flag set by config turned off
+        let mut cfg = crate::config::Config::new(Some(super::CMD_HASH));
+        cfg.set_flag("sort", true);
+        cfg.set_flag("abs", true);
+        let args: Vec<String> = ["--no_sort", "x", "--", "--no_abs"]
+            .iter()
+            .map(|x| (*x).to_string())
+            .collect();
+        let mut m = super::Matches::parse(Some(super::CMD_HASH), &args).unwrap();
+        m.set_config(cfg);
+        assert!(!m.opt_present("sort"));
+        assert!(m.opt_present("abs"));
+        assert_eq!(m.free(), ["x", "--no_abs"]);
+
+        let invalid_list = [
+            ["--sort", "--no_sort"],
+            ["--no_hash_algo", "x"],
+            ["--no_squash", "x"],
+        ];
+        for x in &invalid_list {
+            let args: Vec<String> = x.iter().map(|x| (*x).to_string()).collect();
+            assert!(
+                super::Matches::parse(Some(super::CMD_HASH), &args).is_err(),
+                "{x:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_completion() {
+        for x in super::get_available_shell() {
+            let s = super::get_completion("dirhash-rs", x).unwrap();
+            assert!(s.contains("--hash_algo"), "{x}");
+            assert!(s.contains(super::CMD_DUPES), "{x}");
+        }
+        assert!(super::get_completion("dirhash-rs", "csh").is_err());
+        assert_eq!(super::get_function_name("dirhash-rs"), "_dirhash_rs");
+        assert_eq!(
+            super::get_zsh_string("a [b]: 'c'"),
+            "a \\[b\\]\\: '\\''c'\\''"
+        );
+    }
+}
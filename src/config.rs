@@ -0,0 +1,335 @@
+use crate::cli;
+
+// option defaults in increasing precedence, followed by command line
+pub(crate) const CONFIG_DIR: &str = "dirhash";
+pub(crate) const CONFIG_FILE: &str = "config.toml";
+pub(crate) const TREE_CONFIG_FILE: &str = ".dirhash.toml";
+pub(crate) const ENV_PREFIX: &str = "DIRHASH_";
+
+// options a tree may set for itself, those changing output format, digest
+// or dot and symlink entries ignored, never those reading or writing files
+// other than those walked
+const TREE_KEY: [&str; 16] = [
+    "hash_algo",
+    "ignore_dot",
+    "ignore_dot_dir",
+    "ignore_dot_file",
+    "ignore_symlink",
+    "hash_only",
+    "abs",
+    "swap",
+    "sort",
+    "hashdeep",
+    "archive",
+    "hardlink",
+    "device_number",
+    "special",
+    "chunk_size",
+    "chunk_merkle",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Flag(bool),
+    Str(String),
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    cmd: Option<String>,
+    map: std::collections::HashMap<String, Value>,
+    loaded: Vec<String>, // sources in order loaded
+    tree: Option<String>,
+}
+
+impl Config {
+    pub(crate) fn new(cmd: Option<&str>) -> Self {
+        Self {
+            cmd: cmd.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn get_flag(&self, k: &str) -> Option<bool> {
+        match self.map.get(k) {
+            Some(Value::Flag(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_str(&self, k: &str) -> Option<&str> {
+        match self.map.get(k) {
+            Some(Value::Str(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_loaded(&self) -> &[String] {
+        &self.loaded
+    }
+
+    // per-tree config if loaded, options walked input sets for itself
+    pub(crate) fn get_tree_config(&self) -> Option<&str> {
+        self.tree.as_deref()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_flag(&mut self, k: &str, v: bool) {
+        self.insert(k, Value::Flag(v));
+    }
+
+    // user config, per-tree config of first input, then environment
+    pub(crate) fn load(&mut self, inp: Option<&str>) -> std::io::Result<()> {
+        if let Some(f) = get_user_config_path() {
+            if std::path::Path::new(&f).is_file() {
+                self.load_file(&f, false)?;
+            }
+        }
+        if let Some(f) = inp.and_then(get_tree_config_path) {
+            if std::path::Path::new(&f).is_file() {
+                self.load_file(&f, true)?;
+            }
+        }
+        self.load_env(std::env::vars())
+    }
+
+    fn load_file(&mut self, f: &str, tree: bool) -> std::io::Result<()> {
+        let s = std::fs::read_to_string(f)?;
+        if let Err(e) = self.parse(&s, tree) {
+            return Err(std::io::Error::new(e.kind(), format!("{f}: {e}")));
+        }
+        self.loaded.push(f.to_string());
+        if tree {
+            self.tree = Some(f.to_string());
+        }
+        Ok(())
+    }
+
+    // keys are long option names, unknown keys are errors
+    fn parse(&mut self, s: &str, tree: bool) -> std::io::Result<()> {
+        let t = match s.parse::<toml::Table>() {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    e.message(),
+                ))
+            }
+        };
+        for (k, v) in &t {
+            let Some(flag) = cli::get_arg_flag(k) else {
+                return Err(get_invalid_error(k));
+            };
+            if tree && !TREE_KEY.contains(&k.as_str()) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    format!("{k} not allowed in {TREE_CONFIG_FILE}"),
+                ));
+            }
+            let v = match v {
+                toml::Value::Boolean(v) if flag => Value::Flag(*v),
+                toml::Value::String(v) if !flag => Value::Str(v.clone()),
+                toml::Value::Integer(v) if !flag => Value::Str(v.to_string()),
+                // e.g. hash_algo = ["md5", "sha256"]
+                toml::Value::Array(l) if !flag => {
+                    let mut v = vec![];
+                    for x in l {
+                        let Some(x) = x.as_str() else {
+                            return Err(get_invalid_error(k));
+                        };
+                        v.push(x);
+                    }
+                    Value::Str(v.join(","))
+                }
+                _ => return Err(get_invalid_error(k)),
+            };
+            self.insert(k, v);
+        }
+        Ok(())
+    }
+
+    // e.g. DIRHASH_HASH_ALGO for hash_algo, other variables ignored
+    fn load_env<I: Iterator<Item = (String, String)>>(&mut self, vars: I) -> std::io::Result<()> {
+        let mut found = false;
+        for (k, v) in vars {
+            let Some(k) = k.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let k = k.to_lowercase();
+            let Some(flag) = cli::get_arg_flag(&k) else {
+                continue;
+            };
+            let v = if flag {
+                match v.as_str() {
+                    "1" | "true" | "yes" => Value::Flag(true),
+                    "" | "0" | "false" | "no" => Value::Flag(false),
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("{ENV_PREFIX}{}: invalid {v}", k.to_uppercase()),
+                        ))
+                    }
+                }
+            } else {
+                Value::Str(v)
+            };
+            self.insert(&k, v);
+            found = true;
+        }
+        if found {
+            self.loaded.push(format!("{ENV_PREFIX}*"));
+        }
+        Ok(())
+    }
+
+    // options not taken by command are ignored, so one file serves all
+    fn insert(&mut self, k: &str, v: Value) {
+        if cli::is_command_arg(self.cmd.as_deref(), k) {
+            self.map.insert(k.to_string(), v);
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME/dirhash/config.toml, or under ~/.config if unset
+fn get_user_config_path() -> Option<String> {
+    let d = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(v) if crate::util::is_abspath(&v) => v,
+        _ => format!("{}/.config", std::env::var("HOME").ok()?),
+    };
+    Some(format!("{d}/{CONFIG_DIR}/{CONFIG_FILE}"))
+}
+
+// input directory, or directory containing input file
+fn get_tree_config_path(f: &str) -> Option<String> {
+    let d = if crate::util::get_raw_file_type(f).ok()?.is_dir() {
+        crate::util::get_abspath(f).ok()?
+    } else {
+        crate::util::get_dirpath(f).ok()?
+    };
+    Some(format!("{}/{TREE_CONFIG_FILE}", d.trim_end_matches('/')))
+}
+
+fn get_invalid_error(s: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid {s}"))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_parse() {
+        let mut cfg = super::Config::new(Some(crate::cli::CMD_HASH));
+        cfg.parse(
+            "hash_algo = [\"md5\", \"sha1\"]\nsort = true\nmax_depth = 2\nsquash_depth = 1\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(cfg.get_str("hash_algo"), Some("md5,sha1"));
+        assert_eq!(cfg.get_flag("sort"), Some(true));
+        assert_eq!(cfg.get_str("max_depth"), Some("2"));
+        // not taken by hash
+        assert!(cfg.get_str("squash_depth").is_none());
+
+        // later overrides earlier
+        cfg.parse("sort = false\n", true).unwrap();
+        assert_eq!(cfg.get_flag("sort"), Some(false));
+
+        // per-tree config may set digest and ignored entries
+        for s in [
+            "hash_algo = \"md5\"\n",
+            "ignore_dot = true\n",
+            "ignore_dot_dir = true\n",
+            "ignore_dot_file = true\n",
+            "ignore_symlink = true\n",
+        ] {
+            assert!(cfg.parse(s, true).is_ok(), "{s}");
+        }
+
+        // but may not select files otherwise
+        for s in [
+            "max_depth = 1\n",
+            "type = \"d\"\n",
+            "follow_symlink = true\n",
+        ] {
+            assert_eq!(
+                cfg.parse(s, true).unwrap_err().kind(),
+                std::io::ErrorKind::PermissionDenied,
+                "{s}"
+            );
+            assert!(cfg.parse(s, false).is_ok(), "{s}");
+        }
+
+        let invalid_list = [
+            "xxx = true\n",
+            "sort = \"yes\"\n",
+            "hash_algo = true\n",
+            "hash_algo = [1]\n",
+            "sort = \n",
+        ];
+        for s in &invalid_list {
+            assert!(cfg.parse(s, false).is_err(), "{s}");
+        }
+
+        // per-tree config may not read or write files
+        for s in [
+            "output_file = \"/tmp/x\"\n",
+            "sign = \"/tmp/x\"\n",
+            "hmac_key_file = \"/tmp/x\"\n",
+            "files_from = \"/tmp/x\"\n",
+        ] {
+            assert_eq!(
+                cfg.parse(s, true).unwrap_err().kind(),
+                std::io::ErrorKind::PermissionDenied,
+                "{s}"
+            );
+            assert!(cfg.parse(s, false).is_ok(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_load_tree() {
+        let d = crate::util::TempDir::new("config");
+        let d = d.to_str().unwrap();
+        let f = super::get_tree_config_path(d).unwrap();
+        assert_eq!(f, format!("{d}/{}", super::TREE_CONFIG_FILE));
+        std::fs::write(&f, "hash_algo = \"sha512\"\nsort = true\n").unwrap();
+
+        let mut cfg = super::Config::new(Some(crate::cli::CMD_HASH));
+        cfg.load_file(&f, true).unwrap();
+        assert_eq!(cfg.get_str("hash_algo"), Some("sha512"));
+        assert_eq!(cfg.get_flag("sort"), Some(true));
+        assert_eq!(cfg.get_tree_config(), Some(f.as_str()));
+        assert_eq!(cfg.get_loaded().len(), 1);
+
+        std::fs::write(&f, "output_file = \"/tmp/x\"\n").unwrap();
+        let mut cfg = super::Config::new(Some(crate::cli::CMD_HASH));
+        assert!(cfg.load_file(&f, true).is_err());
+        assert!(cfg.get_tree_config().is_none());
+    }
+
+    #[test]
+    fn test_load_env() {
+        let mut cfg = super::Config::new(None);
+        let vars = [
+            ("DIRHASH_HASH_ALGO", "sha512"),
+            ("DIRHASH_IGNORE_DOT", "1"),
+            ("DIRHASH_HMAC_KEY", "00"),
+            ("HASH_ALGO", "md5"),
+        ];
+        cfg.load_env(
+            vars.iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string())),
+        )
+        .unwrap();
+        assert_eq!(cfg.get_str("hash_algo"), Some("sha512"));
+        assert_eq!(cfg.get_flag("ignore_dot"), Some(true));
+        assert_eq!(cfg.get_loaded(), ["DIRHASH_*"]);
+
+        let vars = [("DIRHASH_SORT", "maybe")];
+        assert!(cfg
+            .load_env(
+                vars.iter()
+                    .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            )
+            .is_err());
+    }
+}
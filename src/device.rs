@@ -0,0 +1,193 @@
+use crate::hash;
+use crate::Opt;
+use std::io::Seek;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+
+// not in libc, built with its _IOR for arch ioctl layout and request type,
+// e.g. c_int on musl
+#[cfg(target_os = "linux")]
+const BLKGETSIZE64: libc::Ioctl = libc::_IOR::<u64>(0x12, 114);
+
+// character devices such as /dev/zero may never reach EOF
+pub(crate) fn is_char_device(f: &str) -> std::io::Result<bool> {
+    Ok(std::fs::metadata(f)?.file_type().is_char_device())
+}
+
+// block device size, None if character device
+pub(crate) fn get_device_size(fp: &std::fs::File) -> std::io::Result<Option<u64>> {
+    if !fp.metadata()?.file_type().is_block_device() {
+        return Ok(None);
+    }
+    get_block_device_size(fp).map(Some)
+}
+
+#[cfg(target_os = "linux")]
+fn get_block_device_size(fp: &std::fs::File) -> std::io::Result<u64> {
+    let mut size: u64 = 0;
+    if unsafe { libc::ioctl(fp.as_raw_fd(), BLKGETSIZE64, &mut size) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_block_device_size(fp: &std::fs::File) -> std::io::Result<u64> {
+    let ret = unsafe { libc::lseek(fp.as_raw_fd(), 0, libc::SEEK_END) };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(ret.try_into().unwrap())
+}
+
+// node label and string hashed in place of content, e.g. "char 1:3 0666"
+pub(crate) fn get_node_string(f: &str) -> std::io::Result<(&'static str, String)> {
+    let m = std::fs::metadata(f)?;
+    let t = m.file_type();
+    let mode = m.mode() & 0o7777;
+    let rdev = m.rdev() as libc::dev_t;
+    let s = if t.is_block_device() {
+        "block"
+    } else if t.is_char_device() {
+        "char"
+    } else if t.is_fifo() {
+        return Ok(("fifo", format!("fifo {mode:04o}")));
+    } else if t.is_socket() {
+        return Ok(("socket", format!("socket {mode:04o}")));
+    } else {
+        return Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+    };
+    let (major, minor) = (libc::major(rdev), libc::minor(rdev));
+    Ok((s, format!("{s} {major}:{minor} {mode:04o}")))
+}
+
+// bytes to read from offset, up to block device size if known
+fn get_device_range(size: Option<u64>, offset: u64, length: Option<u64>) -> (u64, u64) {
+    let n = length.unwrap_or(u64::MAX);
+    match size {
+        Some(v) => (offset, n.min(v.saturating_sub(offset))),
+        None => (offset, n),
+    }
+}
+
+// hash values within --device_offset and --device_length, and block
+// device size if block device, O_DIRECT is not used for devices
+pub(crate) fn get_device_hash(
+    f: &str,
+    hash_algo: &[String],
+    opt: &Opt,
+) -> std::io::Result<(hash::SparseSum, Option<u64>)> {
+    let mut fp = std::fs::File::open(f)?;
+    let size = get_device_size(&fp)?;
+    let (offset, n) = get_device_range(size, opt.device_offset, opt.device_length);
+    // block device rejects seeking past its end
+    if offset > 0 && n > 0 {
+        fp.seek(std::io::SeekFrom::Start(offset))?;
+    }
+    opt.backend.advise_start(&fp);
+    let mut r =
+        std::io::BufReader::with_capacity(opt.backend.buffer_size, std::io::Read::take(&fp, n));
+    let ret = hash::get_hash_multi(&mut r, hash_algo);
+    opt.backend.advise_end(&fp);
+    let (bl, written) = ret?;
+    Ok(((bl, written, 0), size))
+}
+
+#[cfg(test)]
+mod tests {
+    // as in <linux/fs.h> on asm-generic ioctl layout
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    fn test_blkgetsize64() {
+        assert_eq!(super::BLKGETSIZE64 as u32, 0x8008_1272);
+    }
+
+    #[test]
+    fn test_get_device_range() {
+        assert_eq!(super::get_device_range(Some(100), 0, None), (0, 100));
+        assert_eq!(super::get_device_range(Some(100), 10, None), (10, 90));
+        assert_eq!(super::get_device_range(Some(100), 10, Some(20)), (10, 20));
+        assert_eq!(super::get_device_range(Some(100), 90, Some(20)), (90, 10));
+        assert_eq!(super::get_device_range(Some(100), 200, None), (200, 0));
+        assert_eq!(super::get_device_range(None, 0, None), (0, u64::MAX));
+        assert_eq!(super::get_device_range(None, 5, Some(20)), (5, 20));
+    }
+
+    #[test]
+    fn test_get_device_hash() {
+        let mut opt = crate::Opt::default();
+        let hash_algo = [crate::hash::SHA256.to_string()];
+        assert!(super::is_char_device("/dev/zero").unwrap());
+        assert!(!super::is_char_device("/").unwrap());
+
+        // character device reads --device_length bytes
+        opt.device_length = Some(1 << 20);
+        opt.device_offset = 4096;
+        let ((bl, written, _), size) =
+            super::get_device_hash("/dev/zero", &hash_algo, &opt).unwrap();
+        assert!(size.is_none());
+        assert_eq!(written, 1 << 20);
+        let (b, _) = crate::hash::get_byte_hash(&vec![0; 1 << 20], &hash_algo[0]).unwrap();
+        assert_eq!(bl[0], b);
+    }
+
+    #[test]
+    fn test_get_node_string() {
+        assert_eq!(
+            super::get_node_string("/dev/null").unwrap(),
+            ("char", "char 1:3 0666".to_string())
+        );
+        assert_eq!(
+            super::get_node_string("/").unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+
+        let d = crate::util::TempDir::new("fifo");
+        let f = d.join("x");
+        let s = std::ffi::CString::new(f.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(s.as_ptr(), 0o600) }, 0);
+        let p = std::os::unix::fs::PermissionsExt::from_mode(0o640);
+        std::fs::set_permissions(&f, p).unwrap();
+        assert_eq!(
+            super::get_node_string(f.to_str().unwrap()).unwrap(),
+            ("fifo", "fifo 0640".to_string())
+        );
+    }
+
+    #[test]
+    fn test_audit_node() {
+        let d = crate::util::TempDir::new("node");
+        std::fs::write(d.join("a"), "abc").unwrap();
+        let f = d.join("p");
+        let s = std::ffi::CString::new(f.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(s.as_ptr(), 0o600) }, 0);
+        let opt = crate::Opt {
+            hashdeep: true,
+            special: true,
+            hashdeep_algo: vec![
+                crate::hash::MD5.to_string(),
+                crate::hash::SHA256.to_string(),
+            ],
+            ..Default::default()
+        };
+
+        // audited against first walk, changed mode of node fails
+        for (mode, passed) in [(0o600, true), (0o644, false)] {
+            let mut ctx = crate::dir::Context::new();
+            ctx.aud = Some(crate::hashdeep::Audit::new_collect(&opt.hashdeep_algo));
+            let sta = crate::dir::print_input(d.to_str().unwrap(), &mut ctx, &opt).unwrap();
+            assert_eq!(sta.num_stat_special(), 1);
+            ctx.aud.as_mut().unwrap().finish_collect();
+            let p = std::os::unix::fs::PermissionsExt::from_mode(mode);
+            std::fs::set_permissions(&f, p).unwrap();
+            crate::dir::print_input(d.to_str().unwrap(), &mut ctx, &opt).unwrap();
+            assert_eq!(ctx.aud.unwrap().is_passed(), passed, "{mode:o}");
+            std::fs::set_permissions(&f, std::os::unix::fs::PermissionsExt::from_mode(0o600))
+                .unwrap();
+        }
+    }
+}
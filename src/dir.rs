@@ -1,12 +1,129 @@
+use crate::archive;
+use crate::cache;
+use crate::chunk;
+use crate::device;
+use crate::dupes;
+use crate::hardlink;
 use crate::hash;
+use crate::hashdeep;
+use crate::progress;
 use crate::stat;
+use crate::subsquash::SubSquash;
+#[cfg(feature = "io_uring")]
+use crate::uring;
 use crate::util;
 use crate::Opt;
-use crate::Squash;
 use crate::SQUASH_LABEL;
 use crate::SQUASH_VERSION;
 
-pub(crate) fn print_input(f: &str, opt: &Opt) -> std::io::Result<()> {
+const HARDLINK_LABEL: &str = "hardlink";
+const DELETED_LABEL: &str = "deleted";
+
+// walk entries processed at once, files among them may be read ahead
+const WALK_BATCH: usize = 1024;
+
+// state shared across inputs
+#[derive(Debug, Default)]
+pub(crate) struct Context {
+    pub(crate) aud: Option<hashdeep::Audit>,
+    pub(crate) dup: Option<dupes::Dupes>,
+    pub(crate) hln: hardlink::Hardlink,
+    pub(crate) pre: std::collections::HashMap<String, hash::SparseSum>, // read ahead
+    pub(crate) pro: Option<progress::Progress>,
+    pub(crate) cac: Option<cache::Cache>, // kept across walks of same input
+    pub(crate) chk: Option<chunk::Chunk>,
+}
+
+impl Context {
+    pub(crate) fn new() -> Self {
+        Self {
+            hln: hardlink::Hardlink::new(),
+            ..Default::default()
+        }
+    }
+}
+
+// abs input path, archive type if walked as archive, and input prefix
+pub(crate) type Input = (String, Option<archive::ArchiveType>, String);
+
+// stat returned for subsequent changes
+pub(crate) fn print_input(f: &str, ctx: &mut Context, opt: &Opt) -> std::io::Result<stat::Stat> {
+    let mut sta = stat::Stat::new();
+    let Some((f, arc, inp)) = get_input(f, opt)? else {
+        return Ok(sta);
+    };
+
+    // start directory walk
+    let mut squ = SubSquash::new(opt.squash_depth);
+    walk_input(&f, arc.as_ref(), &inp, &mut squ, &mut sta, ctx, opt)?;
+    print_input_stat(&f, &inp, &squ, &mut sta, opt)?;
+    Ok(sta)
+}
+
+// squash buffer of input without printing
+#[cfg(test)]
+pub(crate) fn get_input_squash(f: &str, opt: &Opt) -> std::io::Result<Vec<u8>> {
+    let Some((f, arc, inp)) = get_input(f, opt)? else {
+        return Ok(vec![]);
+    };
+    let mut squ = SubSquash::new(0);
+    let mut sta = stat::Stat::new();
+    walk_input(
+        &f,
+        arc.as_ref(),
+        &inp,
+        &mut squ,
+        &mut sta,
+        &mut Context::new(),
+        opt,
+    )?;
+    Ok(squ.get_buffer())
+}
+
+// single squash hash of all inputs with paths relative to current directory
+pub(crate) fn print_input_list(l: &[String], ctx: &mut Context, opt: &Opt) -> std::io::Result<()> {
+    let cwd = util::canonicalize_path(".")?;
+    let mut squ = SubSquash::new(opt.squash_depth);
+    let mut sta = stat::Stat::new();
+    for f in l {
+        let Some((f, arc, inp)) = get_input(f, opt)? else {
+            continue;
+        };
+        // inputs outside current directory keep their own prefix
+        let inp = if arc.is_none() && cwd != "/" && (f == cwd || f.starts_with(&format!("{cwd}/")))
+        {
+            cwd.clone()
+        } else {
+            inp
+        };
+        walk_input(&f, arc.as_ref(), &inp, &mut squ, &mut sta, ctx, opt)?;
+    }
+    print_input_stat(&cwd, &cwd, &squ, &mut sta, opt)
+}
+
+// walk changed path again, or print it as deleted
+pub(crate) fn print_change(
+    f: &str,
+    inp: &str,
+    sta: &mut stat::Stat,
+    ctx: &mut Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    assert_file_path(f, inp);
+    sta.remove_stat(f);
+    if std::fs::symlink_metadata(f).is_err() {
+        if let Some(v) = &mut ctx.cac {
+            v.remove(f);
+        }
+        let s = format!("{} ({DELETED_LABEL})", get_real_path(f, inp, opt));
+        util::print_record(&s, opt.null);
+        return Ok(());
+    }
+    walk_directory(f, inp, &mut SubSquash::new(0), sta, ctx, opt)
+}
+
+// None if broken symlink
+pub(crate) fn get_input(f: &str, opt: &Opt) -> std::io::Result<Option<Input>> {
     // keep symlink input as is
     // XXX but unlike filepath.WalkDir, walkdir::WalkDir resolves symlink
     let f = if util::get_raw_file_type(f)?.is_symlink() {
@@ -14,7 +131,7 @@ pub(crate) fn print_input(f: &str, opt: &Opt) -> std::io::Result<()> {
     } else {
         let x = util::canonicalize_path(f)?;
         if x.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
         // assert exists
         util::path_exists_or_error(&x)?;
@@ -25,37 +142,71 @@ pub(crate) fn print_input(f: &str, opt: &Opt) -> std::io::Result<()> {
     let f = util::get_abspath(&f)?;
     assert_file_path(&f, "");
 
+    // archive input is walked as if it were a directory
+    let arc = if opt.archive && util::get_raw_file_type(&f)?.is_reg() {
+        archive::get_archive_type(&f)
+    } else {
+        None
+    };
+
     // keep input prefix based on raw type
     let inp = match util::get_raw_file_type(&f)? {
         util::FileType::Dir => f.clone(),
+        util::FileType::Reg if arc.is_some() => f.clone(),
         util::FileType::Reg | util::FileType::Device | util::FileType::Symlink => {
             util::get_dirpath(&f)?
         }
+        util::FileType::Unsupported if opt.special => util::get_dirpath(&f)?,
         _ => return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
     };
 
-    // prefix is a directory
-    assert!(util::get_file_type(&inp)?.is_dir());
+    // prefix is a directory unless archive
+    assert!(arc.is_some() || util::get_file_type(&inp)?.is_dir());
+    Ok(Some((f, arc, inp)))
+}
 
-    // start directory walk
-    let mut squ = Squash::new();
-    let mut sta = stat::Stat::new();
-    walk_directory(&f, &inp, &mut squ, &mut sta, opt)?;
+fn walk_input(
+    f: &str,
+    arc: Option<&archive::ArchiveType>,
+    inp: &str,
+    squ: &mut SubSquash,
+    sta: &mut stat::Stat,
+    ctx: &mut Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    if let Some(t) = arc {
+        walk_archive(f, t, squ, sta, ctx, opt)
+    } else {
+        walk_directory(f, inp, squ, sta, ctx, opt)
+    }
+}
+
+fn print_input_stat(
+    f: &str,
+    inp: &str,
+    squ: &SubSquash,
+    sta: &mut stat::Stat,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    progress::clear_line();
 
     // print various stats
     if opt.verbose {
-        print_verbose_stat(&inp, &mut sta, opt)?;
+        print_verbose_stat(inp, sta, opt)?;
     }
-    sta.print_stat_unsupported(&inp, opt)?;
-    sta.print_stat_invalid(&inp, opt)?;
+    sta.print_stat_unsupported(inp, opt)?;
+    sta.print_stat_invalid(inp, opt)?;
 
     // print squash hash if specified
     if opt.squash {
+        for (x, b) in &squ.get_sub_buffer() {
+            print_subdir_byte(x, b, inp, opt)?;
+        }
         let b = squ.get_buffer();
         if opt.verbose {
             util::print_num_format_string(b.len(), "squashed byte");
         }
-        print_byte(&f, &b, &inp, opt)?;
+        print_byte(f, &b, inp, opt)?;
     }
     Ok(())
 }
@@ -65,28 +216,96 @@ pub(crate) fn print_input(f: &str, opt: &Opt) -> std::io::Result<()> {
 fn walk_directory(
     f: &str,
     inp: &str,
-    squ: &mut Squash,
+    squ: &mut SubSquash,
     sta: &mut stat::Stat,
+    ctx: &mut Context,
     opt: &Opt,
 ) -> std::io::Result<()> {
+    // depth is relative to input prefix, which is parent unless directory
+    // or current directory if input list
+    let offset = if f == inp {
+        0
+    } else if inp == "/" {
+        f[1..].split('/').count()
+    } else {
+        trim_input_prefix(f, inp).split('/').count()
+    };
+    if opt.max_depth < offset {
+        return Ok(());
+    }
+
     let mut l = vec![];
     for entry in walkdir::WalkDir::new(f)
+        .min_depth(opt.min_depth.saturating_sub(offset))
+        .max_depth(opt.max_depth - offset)
         .into_iter()
         .filter_map(std::result::Result::ok)
     {
         let Some(f) = entry.path().to_str() else {
             return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
         };
-        if opt.sort {
-            l.push(f.to_string());
-        } else {
-            walk_directory_impl(f, inp, squ, sta, opt)?;
+        l.push(f.to_string());
+        if !opt.sort && l.len() >= WALK_BATCH {
+            walk_directory_batch(&l, inp, squ, sta, ctx, opt)?;
+            l.clear();
         }
     }
     if opt.sort {
         l.sort();
-        for f in &l {
-            walk_directory_impl(f, inp, squ, sta, opt)?;
+    }
+    walk_directory_batch(&l, inp, squ, sta, ctx, opt)
+}
+
+// output order is walk order regardless of read ahead
+fn walk_directory_batch(
+    l: &[String],
+    inp: &str,
+    squ: &mut SubSquash,
+    sta: &mut stat::Stat,
+    ctx: &mut Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    for l in l.chunks(WALK_BATCH) {
+        #[cfg(feature = "io_uring")]
+        if opt.backend.is_io_uring() {
+            read_ahead(l, ctx, opt)?;
+        }
+        for f in l {
+            walk_directory_impl(f, inp, squ, sta, ctx, opt)?;
+        }
+        ctx.pre.clear();
+    }
+    Ok(())
+}
+
+// hardlinks to hashed inodes reuse hash values, others are read once
+#[cfg(feature = "io_uring")]
+fn read_ahead(l: &[String], ctx: &mut Context, opt: &Opt) -> std::io::Result<()> {
+    // chunked files are read with their chunks
+    if ctx.dup.is_some() || opt.chunk_size > 0 {
+        return Ok(());
+    }
+    let mut fl = vec![];
+    let mut ino = std::collections::HashSet::new();
+    for f in l {
+        let t = util::get_raw_file_type(f)?;
+        if !t.is_reg() || test_ignore_entry(f, &t, opt) || test_filter_entry(f, &t, opt)? {
+            continue;
+        }
+        let m = std::fs::metadata(f)?;
+        if ctx.hln.get_sum(&m).is_some()
+            || (hardlink::is_hardlink(&m) && !ino.insert(cache::get_key(&m)))
+            || m.len() >= u64::try_from(opt.backend.buffer_size).unwrap()
+            || get_cached_sum(f, &m, &opt.hashdeep_algo, ctx).is_some()
+        {
+            continue;
+        }
+        fl.push((f.clone(), m.len()));
+    }
+    let sl = uring::read_files(&fl, &opt.hashdeep_algo, &opt.backend)?;
+    for ((f, _), v) in fl.into_iter().zip(sl) {
+        if let Some(v) = v {
+            ctx.pre.insert(f, v);
         }
     }
     Ok(())
@@ -95,8 +314,9 @@ fn walk_directory(
 fn walk_directory_impl(
     f: &str,
     inp: &str,
-    squ: &mut Squash,
+    squ: &mut SubSquash,
     sta: &mut stat::Stat,
+    ctx: &mut Context,
     opt: &Opt,
 ) -> std::io::Result<()> {
     let mut t = util::get_raw_file_type(f)?;
@@ -105,6 +325,14 @@ fn walk_directory_impl(
         return Ok(());
     }
 
+    // directories are only hashed if squash
+    if test_filter_entry(f, &t, opt)? {
+        sta.append_stat_filtered(f);
+        return Ok(());
+    }
+
+    squ.set_entry(f, t.is_dir(), inp);
+
     // find target if symlink
     // l is symlink itself, not its target
     let (x, l) = if t.is_symlink() {
@@ -113,7 +341,9 @@ fn walk_directory_impl(
             return Ok(());
         }
         if !opt.follow_symlink {
-            print_symlink(f, inp, squ, sta, opt)?;
+            if ctx.dup.is_none() {
+                print_symlink(f, inp, squ, sta, opt)?;
+            }
             return Ok(());
         }
         let x = util::canonicalize_path(f)?;
@@ -131,7 +361,28 @@ fn walk_directory_impl(
 
     match t {
         util::FileType::Dir => handle_directory(&x, &l, inp, squ, sta, opt)?,
-        util::FileType::Reg | util::FileType::Device => print_file(&x, &l, &t, inp, squ, sta, opt)?,
+        util::FileType::Reg if ctx.dup.is_some() => {
+            if let Some(dup) = &mut ctx.dup {
+                append_duplicate(&x, &l, inp, sta, dup, opt)?;
+            }
+        }
+        util::FileType::Device if ctx.dup.is_some() => (),
+        util::FileType::Device if opt.device_number => {
+            print_node(&x, &t, inp, squ, sta, ctx, opt)?;
+        }
+        util::FileType::Device if !opt.char_device && device::is_char_device(&x)? => {
+            sta.append_stat_ignored(f);
+        }
+        util::FileType::Reg | util::FileType::Device => {
+            if opt.hashdeep {
+                print_hashdeep(&x, &l, &t, inp, sta, ctx, opt)?;
+            } else {
+                print_file(&x, &l, &t, inp, squ, sta, ctx, opt)?;
+            }
+        }
+        util::FileType::Unsupported if opt.special && ctx.dup.is_none() => {
+            print_node(&x, &t, inp, squ, sta, ctx, opt)?;
+        }
         util::FileType::Unsupported => print_unsupported(&x, sta, opt)?,
         util::FileType::Invalid => print_invalid(&x, sta, opt)?,
         util::FileType::Symlink => util::panic_file_type(&x, "symlink", &t),
@@ -139,6 +390,76 @@ fn walk_directory_impl(
     Ok(())
 }
 
+// archive root is input prefix, hence member path is "<inp>/<member>"
+// XXX symlinks in archive are never followed
+fn walk_archive(
+    inp: &str,
+    t: &archive::ArchiveType,
+    squ: &mut SubSquash,
+    sta: &mut stat::Stat,
+    ctx: &mut Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    if opt.debug {
+        println!("### {} {}", inp, t.as_str());
+    }
+    let mut l = archive::read_archive(inp, t, &opt.hashdeep_algo, opt.strip_components)?;
+    if opt.sort {
+        l.sort_by(|a, b| a.f.cmp(&b.f));
+    }
+
+    for x in &l {
+        // depth is relative to archive root as if directory input
+        let depth = x.f.split('/').count();
+        if depth < opt.min_depth || depth > opt.max_depth {
+            continue;
+        }
+        let f = format!("{inp}/{}", x.f);
+        if test_ignore_entry(&f, &x.t, opt) {
+            sta.append_stat_ignored(&f);
+            continue;
+        }
+        squ.set_entry(&f, x.t.is_dir(), inp);
+        match x.t {
+            util::FileType::Dir => handle_directory(&f, "", inp, squ, sta, opt)?,
+            util::FileType::Reg => {
+                if opt.debug {
+                    print_debug(&f, &x.t, opt)?;
+                }
+                if opt.hashdeep {
+                    print_hashdeep_sum(
+                        &f,
+                        "",
+                        &x.t,
+                        &x.sum,
+                        x.written,
+                        x.nlink,
+                        inp,
+                        sta,
+                        &mut ctx.aud,
+                        opt,
+                    )?;
+                } else {
+                    print_file_sum(
+                        &f, "", &x.t, &x.sum[0], x.written, x.nlink, inp, squ, sta, opt,
+                    )?;
+                }
+            }
+            util::FileType::Symlink => {
+                if opt.ignore_symlink {
+                    sta.append_stat_ignored(&f);
+                } else {
+                    print_symlink(&f, inp, squ, sta, opt)?;
+                }
+            }
+            util::FileType::Unsupported => print_unsupported(&f, sta, opt)?,
+            util::FileType::Invalid => print_invalid(&f, sta, opt)?,
+            util::FileType::Device => util::panic_file_type(&f, "device", &x.t),
+        }
+    }
+    Ok(())
+}
+
 fn test_ignore_entry(f: &str, t: &util::FileType, opt: &Opt) -> bool {
     assert!(util::is_abspath(f));
 
@@ -170,6 +491,14 @@ fn test_ignore_entry(f: &str, t: &util::FileType, opt: &Opt) -> bool {
     opt.ignore_dot && (base_starts_with_dot || path_contains_slash_dot)
 }
 
+fn test_filter_entry(f: &str, t: &util::FileType, opt: &Opt) -> std::io::Result<bool> {
+    if opt.filter.is_empty() || (t.is_dir() && !opt.squash) {
+        Ok(false)
+    } else {
+        opt.filter.test_entry(f, opt.follow_symlink)
+    }
+}
+
 fn trim_input_prefix<'a>(f: &'a str, inp: &'a str) -> &'a str {
     if f.starts_with(inp) {
         let f = &f[inp.len() + 1..];
@@ -194,6 +523,21 @@ pub(crate) fn get_real_path<'a>(f: &'a str, inp: &'a str, opt: &'a Opt) -> &'a s
     }
 }
 
+// make link -> target format if symlink
+fn get_link_real_path(f: &str, l: &str, inp: &str, opt: &Opt) -> String {
+    let mut realf = get_real_path(f, inp, opt).to_string();
+    if !l.is_empty() {
+        assert_file_path(l, inp);
+        let mut l = l;
+        if !opt.abs {
+            l = trim_input_prefix(l, inp);
+            assert!(!l.starts_with('/'));
+        }
+        realf = format!("{l} -> {realf}");
+    }
+    realf
+}
+
 fn print_byte(f: &str, inb: &[u8], inp: &str, opt: &Opt) -> std::io::Result<()> {
     assert_file_path(f, inp);
 
@@ -208,29 +552,51 @@ fn print_byte(f: &str, inb: &[u8], inp: &str, opt: &Opt) -> std::io::Result<()>
     }
 
     if opt.hash_only {
-        println!("{hex_sum}");
+        util::print_record(&hex_sum, opt.null);
     } else {
         // no space between two
         let s = format!("[{SQUASH_LABEL}][v{SQUASH_VERSION}]");
         let realf = get_real_path(f, inp, opt);
         if realf == "." {
-            println!("{hex_sum}{s}");
+            util::print_record(&format!("{hex_sum}{s}"), opt.null);
         } else {
-            println!(
-                "{}{}",
-                util::get_xsum_format_string(realf, &hex_sum, opt.swap),
-                s
-            );
+            let s = util::get_xsum_format_string(realf, &hex_sum, opt.swap) + &s;
+            util::print_record(&s, opt.null);
         }
     }
     Ok(())
 }
 
+// subdirectory squash hash, always with path unlike print_byte
+fn print_subdir_byte(f: &str, inb: &[u8], inp: &str, opt: &Opt) -> std::io::Result<()> {
+    assert_file_path(f, inp);
+
+    // get hash value
+    let (b, ..) = hash::get_byte_hash(inb, &opt.hash_algo)?;
+    assert!(!b.is_empty());
+    let hex_sum = hash::get_hex_sum(&b);
+
+    // verify hash value if specified
+    if !opt.hash_verify.is_empty() && opt.hash_verify != hex_sum {
+        return Ok(());
+    }
+
+    if opt.hash_only {
+        util::print_record(&hex_sum, opt.null);
+    } else {
+        // no space between two
+        let s = format!("{hex_sum}[{SQUASH_LABEL}][v{SQUASH_VERSION}]");
+        let realf = get_real_path(f, inp, opt);
+        util::print_record(&util::get_xsum_format_string(realf, &s, opt.swap), opt.null);
+    }
+    Ok(())
+}
+
 fn handle_directory<'a>(
     f: &str,
     l: &'a str,
     inp: &'a str,
-    squ: &mut Squash,
+    squ: &mut SubSquash,
     sta: &mut stat::Stat,
     opt: &Opt,
 ) -> std::io::Result<()> {
@@ -275,16 +641,7 @@ fn handle_directory<'a>(
         squ.update_buffer(&b)?;
     } else {
         // make link -> target format if symlink
-        let mut realf = get_real_path(f, inp, opt).to_string();
-        if !l.is_empty() {
-            assert_file_path(l, inp);
-            let mut l = l;
-            if !opt.abs {
-                l = trim_input_prefix(l, inp);
-                assert!(!l.starts_with('/'));
-            }
-            realf = format!("{l} -> {realf}");
-        }
+        let realf = get_link_real_path(f, l, inp, opt);
         let mut v = realf.as_bytes().to_vec();
         v.extend(b);
         squ.update_buffer(&v)?;
@@ -292,13 +649,15 @@ fn handle_directory<'a>(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn print_file<'a>(
     f: &str,
     l: &'a str,
     t: &util::FileType,
     inp: &'a str,
-    squ: &mut Squash,
+    squ: &mut SubSquash,
     sta: &mut stat::Stat,
+    ctx: &mut Context,
     opt: &Opt,
 ) -> std::io::Result<()> {
     assert_file_path(f, inp);
@@ -311,25 +670,393 @@ fn print_file<'a>(
         print_debug(f, t, opt)?;
     }
 
-    // get hash value
-    let (b, written) = hash::get_file_hash(f, &opt.hash_algo)?;
+    if let Some(v) = &mut ctx.pro {
+        v.set_path(f);
+    }
+
+    // get hash value unless hardlink to hashed inode,
+    // or with chunks in one read if chunked
+    let start = std::time::Instant::now();
+    let m = std::fs::metadata(f)?;
+    let mut cl = None;
+    let (b, written) = if opt.chunk_size > 0 && t.is_reg() {
+        let (b, written, l) =
+            chunk::get_chunk_hash(f, &opt.hash_algo, opt.chunk_size, &opt.backend)?;
+        cl = Some(l);
+        (b, written)
+    } else if let Some((mut bl, written, _)) = ctx.pre.remove(f) {
+        ctx.hln.insert_sum(&m, bl.clone(), written);
+        insert_cached_sum(
+            f,
+            &m,
+            std::slice::from_ref(&opt.hash_algo),
+            &bl,
+            written,
+            ctx,
+        );
+        (bl.remove(0), written)
+    } else if let Some(v) = ctx.hln.get_sum(&m) {
+        sta.append_stat_reused(f);
+        (v.0[0].clone(), v.1)
+    } else if let Some(v) = get_cached_sum(f, &m, std::slice::from_ref(&opt.hash_algo), ctx) {
+        sta.append_stat_reused(f);
+        (v.0[0].clone(), v.1)
+    } else {
+        let (mut bl, written, hole) =
+            get_file_sum(f, t, std::slice::from_ref(&opt.hash_algo), sta, opt)?;
+        sta.append_written_hole(hole);
+        ctx.hln.insert_sum(&m, bl.clone(), written);
+        insert_cached_sum(
+            f,
+            &m,
+            std::slice::from_ref(&opt.hash_algo),
+            &bl,
+            written,
+            ctx,
+        );
+        (bl.remove(0), written)
+    };
+    sta.append_time(f, t, start.elapsed(), written);
+    if let Some(v) = &mut ctx.pro {
+        v.append_file(written);
+    }
+    let nlink = hardlink::get_nlink(&m);
+    // only differing byte ranges are printed if verify
+    if ctx.chk.is_some() {
+        append_file_stat(f, t, written, nlink, sta);
+    } else {
+        print_file_sum(f, l, t, &b, written, nlink, inp, squ, sta, opt)?;
+    }
+
+    // chunks follow whole file unless filtered by hash value
+    if let Some(cl) = cl {
+        if opt.hash_verify.is_empty() || opt.hash_verify == hash::get_hex_sum(&b) {
+            print_chunk(f, l, cl, inp, ctx, opt)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_chunk<'a>(
+    f: &str,
+    l: &'a str,
+    bl: Vec<chunk::ChunkSum>,
+    inp: &'a str,
+    ctx: &mut Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    let realf = get_link_real_path(f, l, inp, opt);
+
+    // print differing byte ranges only if verify
+    if let Some(chk) = &mut ctx.chk {
+        let v: Vec<_> = bl
+            .iter()
+            .map(|(start, end, b)| (*start, *end, hash::get_hex_sum(b)))
+            .collect();
+        for (start, end) in chk.verify_chunk(&realf, &v) {
+            util::print_record(&format!("{realf}: {start}-{end}"), opt.null);
+        }
+        return Ok(());
+    }
+
+    if opt.chunk_merkle {
+        let v: Vec<_> = bl.into_iter().map(|x| x.2).collect();
+        let b = chunk::get_merkle_root(&v, &opt.hash_algo)?;
+        let s = chunk::get_merkle_label(opt.chunk_size);
+        print_chunk_record(&realf, &hash::get_hex_sum(&b), &s, opt);
+    } else {
+        for (start, end, b) in &bl {
+            let s = chunk::get_chunk_label(opt.chunk_size, *start, *end);
+            print_chunk_record(&realf, &hash::get_hex_sum(b), &s, opt);
+        }
+    }
+    Ok(())
+}
+
+fn print_chunk_record(realf: &str, hex_sum: &str, label: &str, opt: &Opt) {
+    if opt.hash_only {
+        util::print_record(hex_sum, opt.null);
+    } else {
+        let s = util::get_xsum_format_string(realf, hex_sum, opt.swap) + label;
+        util::print_record(&s, opt.null);
+    }
+}
+
+// devices are read within --device_offset and --device_length
+fn get_file_sum(
+    f: &str,
+    t: &util::FileType,
+    hash_algo: &[String],
+    sta: &mut stat::Stat,
+    opt: &Opt,
+) -> std::io::Result<hash::SparseSum> {
+    if !t.is_device() {
+        return hash::get_file_hash_sparse(f, hash_algo, &opt.backend);
+    }
+    let (v, size) = device::get_device_hash(f, hash_algo, opt)?;
+    if let Some(size) = size {
+        sta.append_device_size(f, size);
+    }
+    Ok(v)
+}
+
+fn get_cached_sum<'a>(
+    f: &str,
+    m: &std::fs::Metadata,
+    hash_algo: &[String],
+    ctx: &'a Context,
+) -> Option<&'a hash::SparseSum> {
+    ctx.cac.as_ref()?.get_sum(f, m, hash_algo)
+}
+
+fn insert_cached_sum(
+    f: &str,
+    m: &std::fs::Metadata,
+    hash_algo: &[String],
+    bl: &[Vec<u8>],
+    written: u64,
+    ctx: &mut Context,
+) {
+    if let Some(v) = &mut ctx.cac {
+        v.insert_sum(f, m, hash_algo, (bl.to_vec(), written, 0));
+    }
+}
+
+// also used for archive members which have no file to read
+#[allow(clippy::too_many_arguments)]
+fn print_file_sum<'a>(
+    f: &str,
+    l: &'a str,
+    t: &util::FileType,
+    b: &[u8],
+    written: u64,
+    nlink: u64,
+    inp: &'a str,
+    squ: &mut SubSquash,
+    sta: &mut stat::Stat,
+    opt: &Opt,
+) -> std::io::Result<()> {
     assert!(!b.is_empty());
-    let hex_sum = hash::get_hex_sum(&b);
+    let hex_sum = hash::get_hex_sum(b);
 
     // count this file
+    append_file_stat(f, t, written, nlink, sta);
+
+    // verify hash value if specified
+    if !opt.hash_verify.is_empty() && opt.hash_verify != hex_sum {
+        return Ok(());
+    }
+
+    // link count tells hardlinked trees from copied ones if specified
+    let hln = if opt.hardlink && nlink > 1 {
+        format!("[{HARDLINK_LABEL}]")
+    } else {
+        String::new()
+    };
+
+    // squash or print this file
+    if opt.hash_only {
+        if opt.squash {
+            let mut v = b.to_vec();
+            if !hln.is_empty() {
+                v.extend(nlink.to_string().as_bytes());
+            }
+            squ.update_buffer(&v)?;
+        } else {
+            util::print_record(&hex_sum, opt.null);
+        }
+    } else {
+        // make link -> target format if symlink
+        let realf = get_link_real_path(f, l, inp, opt);
+        if opt.squash {
+            let mut v = realf.as_bytes().to_vec();
+            v.extend(b);
+            if !hln.is_empty() {
+                v.extend(nlink.to_string().as_bytes());
+            }
+            squ.update_buffer(&v)?;
+        } else {
+            let s = util::get_xsum_format_string(&realf, &hex_sum, opt.swap) + &hln;
+            util::print_record(&s, opt.null);
+        }
+    }
+    Ok(())
+}
+
+fn append_file_stat(f: &str, t: &util::FileType, written: u64, nlink: u64, sta: &mut stat::Stat) {
     sta.append_stat_total();
     sta.append_written_total(written);
     match t {
         util::FileType::Reg => {
             sta.append_stat_regular(f);
             sta.append_written_regular(written);
+            if nlink > 1 {
+                sta.append_stat_hardlink(f);
+            }
         }
         util::FileType::Device => {
             sta.append_stat_device(f);
             sta.append_written_device(written);
         }
+        // FIFO or socket, nothing read
+        util::FileType::Unsupported => sta.append_stat_special(f),
         _ => util::panic_file_type(f, "invalid", t),
     }
+}
+
+fn print_hashdeep<'a>(
+    f: &str,
+    l: &'a str,
+    t: &util::FileType,
+    inp: &'a str,
+    sta: &mut stat::Stat,
+    ctx: &mut Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    assert_file_path(f, inp);
+    if !l.is_empty() {
+        assert_file_path(l, inp);
+    }
+
+    // debug print first
+    if opt.debug {
+        print_debug(f, t, opt)?;
+    }
+
+    if let Some(v) = &mut ctx.pro {
+        v.set_path(f);
+    }
+
+    // get hash values unless hardlink to hashed inode
+    let start = std::time::Instant::now();
+    let m = std::fs::metadata(f)?;
+    let (bl, written) = if let Some((bl, written, _)) = ctx.pre.remove(f) {
+        ctx.hln.insert_sum(&m, bl.clone(), written);
+        insert_cached_sum(f, &m, &opt.hashdeep_algo, &bl, written, ctx);
+        (bl, written)
+    } else if let Some(v) = ctx.hln.get_sum(&m) {
+        sta.append_stat_reused(f);
+        v.clone()
+    } else if let Some(v) = get_cached_sum(f, &m, &opt.hashdeep_algo, ctx) {
+        sta.append_stat_reused(f);
+        (v.0.clone(), v.1)
+    } else {
+        let (bl, written, hole) = get_file_sum(f, t, &opt.hashdeep_algo, sta, opt)?;
+        sta.append_written_hole(hole);
+        ctx.hln.insert_sum(&m, bl.clone(), written);
+        insert_cached_sum(f, &m, &opt.hashdeep_algo, &bl, written, ctx);
+        (bl, written)
+    };
+    sta.append_time(f, t, start.elapsed(), written);
+    if let Some(v) = &mut ctx.pro {
+        v.append_file(written);
+    }
+    let nlink = hardlink::get_nlink(&m);
+    print_hashdeep_sum(f, l, t, &bl, written, nlink, inp, sta, &mut ctx.aud, opt)
+}
+
+// also used for archive members which have no file to read
+#[allow(clippy::too_many_arguments)]
+fn print_hashdeep_sum<'a>(
+    f: &str,
+    l: &'a str,
+    t: &util::FileType,
+    bl: &[Vec<u8>],
+    written: u64,
+    nlink: u64,
+    inp: &'a str,
+    sta: &mut stat::Stat,
+    aud: &mut Option<hashdeep::Audit>,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    assert_eq!(bl.len(), opt.hashdeep_algo.len());
+    let hex_sum: Vec<String> = bl.iter().map(|b| hash::get_hex_sum(b)).collect();
+
+    // count this file
+    append_file_stat(f, t, written, nlink, sta);
+
+    // verify hash value if specified
+    if !opt.hash_verify.is_empty() && !hex_sum.contains(&opt.hash_verify) {
+        return Ok(());
+    }
+
+    // use symlink itself rather than link -> target format
+    let realf = if l.is_empty() {
+        get_real_path(f, inp, opt)
+    } else if opt.abs {
+        l
+    } else {
+        trim_input_prefix(l, inp)
+    };
+
+    // audit or print this file
+    if let Some(aud) = aud {
+        aud.audit_record(realf, written, &hex_sum);
+    } else {
+        util::print_record(
+            &hashdeep::get_record_string(realf, written, &hex_sum),
+            opt.null,
+        );
+    }
+    Ok(())
+}
+
+// defer hashing until all inputs are walked
+fn append_duplicate<'a>(
+    f: &str,
+    l: &'a str,
+    inp: &'a str,
+    sta: &mut stat::Stat,
+    dup: &mut dupes::Dupes,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    assert_file_path(f, inp);
+    if !l.is_empty() {
+        assert_file_path(l, inp);
+    }
+
+    // debug print first
+    if opt.debug {
+        print_debug(f, &util::FileType::Reg, opt)?;
+    }
+
+    // count this file, but nothing hashed yet
+    sta.append_stat_total();
+    sta.append_stat_regular(f);
+
+    dup.append_entry(f, &get_link_real_path(f, l, inp, opt))
+}
+
+fn print_symlink(
+    f: &str,
+    inp: &str,
+    squ: &mut SubSquash,
+    sta: &mut stat::Stat,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    assert_file_path(f, inp);
+
+    // debug print first
+    if opt.debug {
+        print_debug(f, &util::FileType::Symlink, opt)?;
+    }
+
+    // get hash value of symlink base name
+    // --hash_algo may list multiple algorithms if hashdeep
+    let (b, written) = hash::get_string_hash(&util::get_basename(f)?, &opt.hashdeep_algo[0])?;
+    assert!(!b.is_empty());
+    let hex_sum = hash::get_hex_sum(&b);
+
+    // count this file
+    sta.append_stat_total();
+    sta.append_written_total(written);
+    sta.append_stat_symlink(f);
+    sta.append_written_symlink(written);
+
+    // hashdeep format has no symlink record
+    if opt.hashdeep {
+        return Ok(());
+    }
 
     // verify hash value if specified
     if !opt.hash_verify.is_empty() && opt.hash_verify != hex_sum {
@@ -341,58 +1068,58 @@ fn print_file<'a>(
         if opt.squash {
             squ.update_buffer(&b)?;
         } else {
-            println!("{hex_sum}");
+            util::print_record(&hex_sum, opt.null);
         }
     } else {
-        // make link -> target format if symlink
-        let mut realf = get_real_path(f, inp, opt).to_string();
-        if !l.is_empty() {
-            assert_file_path(l, inp);
-            let mut l = l;
-            if !opt.abs {
-                l = trim_input_prefix(l, inp);
-                assert!(!l.starts_with('/'));
-            }
-            realf = format!("{l} -> {realf}");
-        }
+        let realf = get_real_path(f, inp, opt);
         if opt.squash {
             let mut v = realf.as_bytes().to_vec();
             v.extend(b);
             squ.update_buffer(&v)?;
         } else {
-            println!(
-                "{}",
-                util::get_xsum_format_string(&realf, &hex_sum, opt.swap)
+            util::print_record(
+                &util::get_xsum_format_string(realf, &hex_sum, opt.swap),
+                opt.null,
             );
         }
     }
     Ok(())
 }
 
-fn print_symlink(
+// device, FIFO or socket hashed by its type and permissions, and
+// device numbers if device, as content is not meaningful
+fn print_node(
     f: &str,
+    t: &util::FileType,
     inp: &str,
-    squ: &mut Squash,
+    squ: &mut SubSquash,
     sta: &mut stat::Stat,
+    ctx: &mut Context,
     opt: &Opt,
 ) -> std::io::Result<()> {
     assert_file_path(f, inp);
 
     // debug print first
     if opt.debug {
-        print_debug(f, &util::FileType::Symlink, opt)?;
+        print_debug(f, t, opt)?;
     }
 
-    // get hash value of symlink base name
-    let (b, written) = hash::get_string_hash(&util::get_basename(f)?, &opt.hash_algo)?;
+    // hashdeep record of size 0 with digest of node string per algorithm,
+    // so that check and diff see nodes
+    let (label, s) = device::get_node_string(f)?;
+    if opt.hashdeep {
+        let mut bl = vec![];
+        for x in &opt.hashdeep_algo {
+            bl.push(hash::get_string_hash(&s, x)?.0);
+        }
+        return print_hashdeep_sum(f, "", t, &bl, 0, 1, inp, sta, &mut ctx.aud, opt);
+    }
+    let (b, ..) = hash::get_string_hash(&s, &opt.hashdeep_algo[0])?;
     assert!(!b.is_empty());
     let hex_sum = hash::get_hex_sum(&b);
 
-    // count this file
-    sta.append_stat_total();
-    sta.append_written_total(written);
-    sta.append_stat_symlink(f);
-    sta.append_written_symlink(written);
+    // count this file, but nothing read
+    append_file_stat(f, t, 0, 1, sta);
 
     // verify hash value if specified
     if !opt.hash_verify.is_empty() && opt.hash_verify != hex_sum {
@@ -404,7 +1131,7 @@ fn print_symlink(
         if opt.squash {
             squ.update_buffer(&b)?;
         } else {
-            println!("{hex_sum}");
+            util::print_record(&hex_sum, opt.null);
         }
     } else {
         let realf = get_real_path(f, inp, opt);
@@ -413,10 +1140,8 @@ fn print_symlink(
             v.extend(b);
             squ.update_buffer(&v)?;
         } else {
-            println!(
-                "{}",
-                util::get_xsum_format_string(realf, &hex_sum, opt.swap)
-            );
+            let s = util::get_xsum_format_string(realf, &hex_sum, opt.swap) + &format!("[{label}]");
+            util::print_record(&s, opt.null);
         }
     }
     Ok(())
@@ -448,7 +1173,11 @@ fn print_debug(f: &str, t: &util::FileType, opt: &Opt) -> std::io::Result<()> {
     Ok(())
 }
 
-fn print_verbose_stat(inp: &str, sta: &mut stat::Stat, opt: &Opt) -> std::io::Result<()> {
+pub(crate) fn print_verbose_stat(
+    inp: &str,
+    sta: &mut stat::Stat,
+    opt: &Opt,
+) -> std::io::Result<()> {
     let indent = " ";
 
     util::print_num_format_string(sta.num_stat_total(), "file");
@@ -456,7 +1185,8 @@ fn print_verbose_stat(inp: &str, sta: &mut stat::Stat, opt: &Opt) -> std::io::Re
     let a1 = sta.num_stat_regular();
     let a2 = sta.num_stat_device();
     let a3 = sta.num_stat_symlink();
-    assert_eq!(a0 + a1 + a2 + a3, sta.num_stat_total());
+    let a4 = sta.num_stat_special();
+    assert_eq!(a0 + a1 + a2 + a3 + a4, sta.num_stat_total());
     if a0 > 0 {
         print!("{indent}");
         util::print_num_format_string(a0, util::FileType::Dir.as_str());
@@ -473,6 +1203,10 @@ fn print_verbose_stat(inp: &str, sta: &mut stat::Stat, opt: &Opt) -> std::io::Re
         print!("{indent}");
         util::print_num_format_string(a3, util::FileType::Symlink.as_str());
     }
+    if a4 > 0 {
+        print!("{indent}");
+        util::print_num_format_string(a4, "special file");
+    }
 
     util::print_num_format_string(sta.num_written_total(), "byte");
     let b0 = sta.num_written_directory();
@@ -503,7 +1237,83 @@ fn print_verbose_stat(inp: &str, sta: &mut stat::Stat, opt: &Opt) -> std::io::Re
         );
     }
 
-    sta.print_stat_ignored(inp, opt)
+    let h = sta.num_written_hole();
+    if h > 0 {
+        util::print_num_format_string(h, "skipped hole byte");
+        assert!(h <= b1);
+    }
+
+    let c0 = sta.num_stat_hardlink();
+    let c1 = sta.num_stat_reused();
+    if c0 > 0 {
+        util::print_num_format_string(c0, "hardlinked file");
+    }
+    if c1 > 0 {
+        util::print_num_format_string(c1, "reused hash");
+        assert!(c1 <= a1 + a2);
+    }
+
+    // hash time excludes archive members and files read ahead
+    let d0 = sta.num_time_wall();
+    let d1 = sta.num_time_walk();
+    let d2 = sta.num_time_hash();
+    println!("{d0:.3?} wall time");
+    println!("{indent}{d1:.3?} walk time");
+    println!("{indent}{d2:.3?} hash time");
+    let e1 = sta.num_time_regular();
+    let e2 = sta.num_time_device();
+    if !e1.is_zero() {
+        println!(
+            "{:.1} MB/s {} throughput",
+            get_throughput(b1, e1),
+            util::FileType::Reg.as_str()
+        );
+    }
+    if !e2.is_zero() {
+        println!(
+            "{:.1} MB/s {} throughput",
+            get_throughput(b2, e2),
+            util::FileType::Device.as_str()
+        );
+    }
+
+    let l = sta.get_slowest();
+    if !l.is_empty() {
+        util::print_num_format_string(l.len(), "slowest file");
+        for (d, f) in l {
+            println!("{indent}{d:.3?} {}", get_real_path(f, inp, opt));
+        }
+    }
+    let l = sta.get_largest();
+    if !l.is_empty() {
+        util::print_num_format_string(l.len(), "largest file");
+        for (n, f) in l {
+            println!(
+                "{indent}{} {}",
+                util::get_num_format_string(usize::try_from(*n).unwrap(), "byte"),
+                get_real_path(f, inp, opt)
+            );
+        }
+    }
+
+    let l = sta.get_device_size();
+    if !l.is_empty() {
+        util::print_num_format_string(l.len(), "block device");
+        for (n, f) in l {
+            println!(
+                "{indent}{} {}",
+                util::get_num_format_string(usize::try_from(*n).unwrap(), "byte"),
+                get_real_path(f, inp, opt)
+            );
+        }
+    }
+
+    sta.print_stat_ignored(inp, opt)?;
+    sta.print_stat_filtered(inp, opt)
+}
+
+fn get_throughput(written: usize, d: std::time::Duration) -> f64 {
+    written as f64 / d.as_secs_f64() / 1_000_000.0
 }
 
 fn assert_file_path(f: &str, inp: &str) {
@@ -0,0 +1,264 @@
+use std::io::Read;
+use std::io::Seek;
+use std::os::unix::fs::MetadataExt;
+
+use crate::backend;
+use crate::hash;
+use crate::util;
+use crate::Opt;
+
+const PARTIAL_SIZE: u64 = 4096;
+
+#[derive(Debug)]
+struct Entry {
+    f: String,     // path to read
+    realf: String, // path to print
+    size: u64,
+    ino: (u64, u64),
+}
+
+#[derive(Debug)]
+pub(crate) struct Group {
+    pub(crate) hex_sum: String,
+    pub(crate) size: u64,
+    pub(crate) realf: Vec<String>, // one path per inode
+}
+
+impl Group {
+    pub(crate) fn get_wasted(&self) -> u64 {
+        self.size * u64::try_from(self.realf.len() - 1).unwrap()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Dupes {
+    entry: Vec<Entry>,
+    hardlink: Vec<Vec<String>>,
+
+    num_partial: usize,
+    num_full: usize,
+    written: u64,
+}
+
+impl Dupes {
+    pub(crate) fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn append_entry(&mut self, f: &str, realf: &str) -> std::io::Result<()> {
+        let m = std::fs::metadata(f)?;
+        self.entry.push(Entry {
+            f: f.to_string(),
+            realf: realf.to_string(),
+            size: m.len(),
+            ino: (m.dev(), m.ino()),
+        });
+        Ok(())
+    }
+
+    pub(crate) fn num_entry(&self) -> usize {
+        self.entry.len()
+    }
+
+    // files with unique size are never read
+    pub(crate) fn get_duplicates(
+        &mut self,
+        hash_algo: &str,
+        partial: bool,
+        bk: &backend::Backend,
+    ) -> std::io::Result<Vec<Group>> {
+        let mut size_map = std::collections::BTreeMap::<u64, Vec<usize>>::new();
+        for (i, x) in self.entry.iter().enumerate() {
+            // empty files waste nothing
+            if x.size > 0 {
+                size_map.entry(x.size).or_default().push(i);
+            }
+        }
+
+        let mut dup = vec![];
+        self.hardlink.clear();
+        for (size, l) in size_map.iter().rev() {
+            // collapse hardlinks into one candidate per inode, in walk order
+            let mut ino_list: Vec<Vec<usize>> = vec![];
+            let mut ino_map = std::collections::HashMap::<(u64, u64), usize>::new();
+            for &i in l {
+                match ino_map.get(&self.entry[i].ino) {
+                    Some(&j) => ino_list[j].push(i),
+                    None => {
+                        ino_map.insert(self.entry[i].ino, ino_list.len());
+                        ino_list.push(vec![i]);
+                    }
+                }
+            }
+            for v in &ino_list {
+                if v.len() > 1 {
+                    self.hardlink
+                        .push(v.iter().map(|&i| self.entry[i].realf.clone()).collect());
+                }
+            }
+            if ino_list.len() < 2 {
+                continue;
+            }
+
+            // split candidates by first and last blocks if specified
+            let mut cand = vec![ino_list.iter().map(|v| v[0]).collect::<Vec<usize>>()];
+            if partial && *size > PARTIAL_SIZE * 2 {
+                cand = self.split_candidate(&cand[0], |x| get_partial_hash(&x.f, hash_algo))?;
+            }
+
+            for l in &cand {
+                let mut m = std::collections::BTreeMap::<String, Vec<usize>>::new();
+                for &i in l {
+                    let (b, written) = hash::get_file_hash(&self.entry[i].f, hash_algo, bk)?;
+                    self.num_full += 1;
+                    self.written += written;
+                    m.entry(hash::get_hex_sum(&b)).or_default().push(i);
+                }
+                for (hex_sum, v) in m {
+                    if v.len() > 1 {
+                        dup.push(Group {
+                            hex_sum,
+                            size: *size,
+                            realf: v.iter().map(|&i| self.entry[i].realf.clone()).collect(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(dup)
+    }
+
+    fn split_candidate(
+        &mut self,
+        l: &[usize],
+        f: impl Fn(&Entry) -> std::io::Result<(Vec<u8>, u64)>,
+    ) -> std::io::Result<Vec<Vec<usize>>> {
+        let mut m = std::collections::BTreeMap::<Vec<u8>, Vec<usize>>::new();
+        for &i in l {
+            let (b, written) = f(&self.entry[i])?;
+            self.num_partial += 1;
+            self.written += written;
+            m.entry(b).or_default().push(i);
+        }
+        Ok(m.into_values().filter(|v| v.len() > 1).collect())
+    }
+
+    pub(crate) fn print_duplicates(&mut self, opt: &Opt) -> std::io::Result<()> {
+        let dup = self.get_duplicates(&opt.hash_algo, opt.duplicates_partial, &opt.backend)?;
+
+        let mut wasted = 0;
+        for g in &dup {
+            let s = format!(
+                "# {}, {} each, {} wasted",
+                util::get_num_format_string(g.realf.len(), "file"),
+                util::get_num_format_string(usize::try_from(g.size).unwrap(), "byte"),
+                util::get_num_format_string(usize::try_from(g.get_wasted()).unwrap(), "byte"),
+            );
+            util::print_record(&s, opt.null);
+            for f in &g.realf {
+                util::print_record(
+                    &util::get_xsum_format_string(f, &g.hex_sum, opt.swap),
+                    opt.null,
+                );
+            }
+            util::print_record("", opt.null);
+            wasted += g.get_wasted();
+        }
+        for l in &self.hardlink {
+            let s = format!(
+                "# {} to same inode",
+                util::get_num_format_string(l.len(), "hardlink")
+            );
+            util::print_record(&s, opt.null);
+            for f in l {
+                util::print_record(f, opt.null);
+            }
+            util::print_record("", opt.null);
+        }
+
+        util::print_num_format_string(dup.len(), "duplicate set");
+        util::print_num_format_string(usize::try_from(wasted).unwrap(), "wasted byte");
+        if opt.verbose {
+            util::print_num_format_string(self.num_entry(), "examined file");
+            util::print_num_format_string(self.num_partial, "partially hashed file");
+            util::print_num_format_string(self.num_full, "fully hashed file");
+            util::print_num_format_string(usize::try_from(self.written).unwrap(), "byte");
+        }
+        Ok(())
+    }
+}
+
+fn get_partial_hash(f: &str, hash_algo: &str) -> std::io::Result<(Vec<u8>, u64)> {
+    let mut fp = std::fs::File::open(f)?;
+    let mut b = vec![0; usize::try_from(PARTIAL_SIZE * 2).unwrap()];
+    let (head, tail) = b.split_at_mut(usize::try_from(PARTIAL_SIZE).unwrap());
+    fp.read_exact(head)?;
+    fp.seek(std::io::SeekFrom::End(
+        -i64::try_from(PARTIAL_SIZE).unwrap(),
+    ))?;
+    fp.read_exact(tail)?;
+    hash::get_byte_hash(&b, hash_algo)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_duplicates() {
+        let d = crate::util::TempDir::new("dupes");
+        let big_a = "a".repeat(10000);
+        let mut big_b = big_a.clone();
+        big_b.replace_range(9999..10000, "b");
+        let file_list = [
+            ("a1", "abc"),
+            ("a2", "abc"),
+            ("b", "abd"),
+            ("c", "unique size"),
+            ("e1", ""),
+            ("e2", ""),
+            ("big_a1", &big_a),
+            ("big_a2", &big_a),
+            ("big_b", &big_b),
+        ];
+        for x in &file_list {
+            std::fs::write(d.join(x.0), x.1).unwrap();
+        }
+        std::fs::hard_link(d.join("a1"), d.join("a3")).unwrap();
+
+        for (partial, bk) in [
+            (false, crate::backend::Backend::default()),
+            (true, crate::backend::Backend::default()),
+            (
+                true,
+                crate::backend::Backend::new(crate::backend::MMAP, 4096, true).unwrap(),
+            ),
+        ] {
+            let mut dupes = super::Dupes::new();
+            let mut l: Vec<&str> = file_list.iter().map(|x| x.0).collect();
+            l.push("a3");
+            for x in &l {
+                let f = d.join(x);
+                dupes.append_entry(f.to_str().unwrap(), x).unwrap();
+            }
+            assert_eq!(dupes.num_entry(), 10);
+
+            let dup = match dupes.get_duplicates(crate::hash::SHA256, partial, &bk) {
+                Ok(v) => v,
+                Err(e) => panic!("{e}"),
+            };
+            assert_eq!(dup.len(), 2);
+            assert_eq!(dup[0].size, 10000);
+            assert_eq!(dup[0].realf, ["big_a1", "big_a2"]);
+            assert_eq!(dup[0].get_wasted(), 10000);
+            assert_eq!(dup[1].size, 3);
+            assert_eq!(dup[1].realf, ["a1", "a2"]);
+            assert_eq!(dup[1].get_wasted(), 3);
+            assert_eq!(dupes.hardlink, [["a1", "a3"]]);
+
+            // "c" and empty files never read, "big_b" not fully read if partial
+            assert_eq!(dupes.num_full, if partial { 5 } else { 6 });
+            assert_eq!(dupes.num_partial, if partial { 3 } else { 0 });
+        }
+    }
+}
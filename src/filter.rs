@@ -0,0 +1,351 @@
+use std::os::unix::fs::FileTypeExt;
+
+pub(crate) const TYPE_REGULAR: char = 'f';
+pub(crate) const TYPE_DIRECTORY: char = 'd';
+pub(crate) const TYPE_SYMLINK: char = 'l';
+pub(crate) const TYPE_BLOCK: char = 'b';
+pub(crate) const TYPE_CHAR: char = 'c';
+pub(crate) const TYPE_FIFO: char = 'p';
+pub(crate) const TYPE_SOCKET: char = 's';
+
+pub(crate) fn get_available_type() -> [char; 7] {
+    [
+        TYPE_REGULAR,
+        TYPE_DIRECTORY,
+        TYPE_SYMLINK,
+        TYPE_BLOCK,
+        TYPE_CHAR,
+        TYPE_FIFO,
+        TYPE_SOCKET,
+    ]
+}
+
+// entries not matching every specified condition are filtered
+#[derive(Debug, Default)]
+pub(crate) struct Filter {
+    pub(crate) min_size: Option<u64>, // regular files only
+    pub(crate) max_size: Option<u64>, // regular files only
+    pub(crate) newer_than: Option<std::time::SystemTime>, // non directories only
+    pub(crate) older_than: Option<std::time::SystemTime>, // non directories only
+    pub(crate) type_list: Vec<char>,
+}
+
+impl Filter {
+    pub(crate) fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min_size.is_none()
+            && self.max_size.is_none()
+            && self.newer_than.is_none()
+            && self.older_than.is_none()
+            && self.type_list.is_empty()
+    }
+
+    // symlink is tested against its target if followed
+    pub(crate) fn test_entry(&self, f: &str, follow_symlink: bool) -> std::io::Result<bool> {
+        let m = if follow_symlink {
+            match std::fs::metadata(f) {
+                Ok(v) => v,
+                Err(_) => std::fs::symlink_metadata(f)?, // e.g. broken symlink
+            }
+        } else {
+            std::fs::symlink_metadata(f)?
+        };
+        let t = m.file_type();
+
+        if !self.type_list.is_empty() && !self.type_list.contains(&get_type_char(&t)) {
+            return Ok(true);
+        }
+        if t.is_dir() {
+            return Ok(false);
+        }
+
+        if t.is_file() {
+            if matches!(self.min_size, Some(v) if m.len() < v) {
+                return Ok(true);
+            }
+            if matches!(self.max_size, Some(v) if m.len() > v) {
+                return Ok(true);
+            }
+        }
+
+        if self.newer_than.is_some() || self.older_than.is_some() {
+            let mtime = m.modified()?;
+            if matches!(self.newer_than, Some(v) if mtime <= v) {
+                return Ok(true);
+            }
+            if matches!(self.older_than, Some(v) if mtime >= v) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn get_type_char(t: &std::fs::FileType) -> char {
+    if t.is_dir() {
+        TYPE_DIRECTORY
+    } else if t.is_file() {
+        TYPE_REGULAR
+    } else if t.is_symlink() {
+        TYPE_SYMLINK
+    } else if t.is_block_device() {
+        TYPE_BLOCK
+    } else if t.is_char_device() {
+        TYPE_CHAR
+    } else if t.is_fifo() {
+        TYPE_FIFO
+    } else if t.is_socket() {
+        TYPE_SOCKET
+    } else {
+        '?'
+    }
+}
+
+// bytes with optional K, M or G suffix in 1024 units
+pub(crate) fn parse_size(s: &str) -> std::io::Result<u64> {
+    let (s, unit) = match s.chars().last() {
+        Some('K' | 'k') => (&s[..s.len() - 1], 1 << 10),
+        Some('M' | 'm') => (&s[..s.len() - 1], 1 << 20),
+        Some('G' | 'g') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    match s.parse::<u64>() {
+        Ok(v) => match v.checked_mul(unit) {
+            Some(v) => Ok(v),
+            None => Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+        },
+        Err(_) => Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+    }
+}
+
+// mtime of existing file, unix time in seconds, or YYYY-MM-DD[THH:MM:SS] in UTC
+pub(crate) fn parse_time(s: &str) -> std::io::Result<std::time::SystemTime> {
+    if let Ok(m) = std::fs::metadata(s) {
+        return m.modified();
+    }
+    let t = match s.parse::<i64>() {
+        Ok(v) => v,
+        Err(_) => parse_date(s)?,
+    };
+    let d = std::time::Duration::from_secs(t.unsigned_abs());
+    let t = if t >= 0 {
+        std::time::UNIX_EPOCH.checked_add(d)
+    } else {
+        std::time::UNIX_EPOCH.checked_sub(d)
+    };
+    match t {
+        Some(v) => Ok(v),
+        None => Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+    }
+}
+
+fn parse_date(s: &str) -> std::io::Result<i64> {
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((a, b)) => (a, b),
+        None => (s, "00:00:00"),
+    };
+    let dl: Vec<&str> = date.split('-').collect();
+    let tl: Vec<&str> = time.split(':').collect();
+    if dl.len() != 3 || dl[0].len() != 4 || tl.len() != 3 {
+        return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+    }
+    let mut v = vec![];
+    for x in dl.iter().chain(tl.iter()) {
+        match x.parse::<i64>() {
+            Ok(n) if x.chars().all(|c| c.is_ascii_digit()) => v.push(n),
+            _ => return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+        }
+    }
+    if !(1..=12).contains(&v[1])
+        || !(1..=31).contains(&v[2])
+        || !(0..24).contains(&v[3])
+        || !(0..60).contains(&v[4])
+        || !(0..60).contains(&v[5])
+    {
+        return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+    }
+    Ok(get_days_from_civil(v[0], v[1], v[2]) * 86400 + v[3] * 3600 + v[4] * 60 + v[5])
+}
+
+// days since 1970-01-01 in proleptic Gregorian calendar
+fn get_days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// comma separated type characters
+pub(crate) fn parse_type(s: &str) -> std::io::Result<Vec<char>> {
+    let mut l = vec![];
+    for x in s.split(',') {
+        let mut c = x.chars();
+        match (c.next(), c.next()) {
+            (Some(v), None) if get_available_type().contains(&v) => {
+                if !l.contains(&v) {
+                    l.push(v);
+                }
+            }
+            _ => return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+        }
+    }
+    Ok(l)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_parse_size() {
+        let size_list = [
+            ("0", 0),
+            ("123", 123),
+            ("1K", 1024),
+            ("2k", 2048),
+            ("3M", 3 << 20),
+            ("4G", 4 << 30),
+        ];
+        for x in &size_list {
+            match super::parse_size(x.0) {
+                Ok(v) => assert_eq!(v, x.1, "{}", x.0),
+                Err(e) => panic!("{e}"),
+            }
+        }
+
+        let invalid_list = ["", "K", "-1", "1.5K", "1KB", "1T", "99999999999999999999G"];
+        for s in &invalid_list {
+            if let Ok(v) = super::parse_size(s) {
+                panic!("{s} {v}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let time_list = [
+            ("0", 0),
+            ("1700000000", 1_700_000_000),
+            ("-86400", -86400),
+            ("1970-01-01", 0),
+            ("2000-02-29", 951_782_400),
+            ("2024-01-01T12:34:56", 1_704_112_496),
+            ("2024-01-01 12:34:56", 1_704_112_496),
+        ];
+        for x in &time_list {
+            let t = match super::parse_time(x.0) {
+                Ok(v) => v,
+                Err(e) => panic!("{} {e}", x.0),
+            };
+            let n: i64 = if x.1 >= 0 {
+                t.duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+                    .try_into()
+                    .unwrap()
+            } else {
+                -i64::try_from(std::time::UNIX_EPOCH.duration_since(t).unwrap().as_secs()).unwrap()
+            };
+            assert_eq!(n, x.1, "{}", x.0);
+        }
+
+        let invalid_list = [
+            "",
+            "xxx",
+            "2024-13-01",
+            "2024-01-32",
+            "2024-01-01T24:00:00",
+            "2024-01-01T12:34",
+            "24-01-01",
+            "2024-+1-01",
+        ];
+        for s in &invalid_list {
+            if let Ok(v) = super::parse_time(s) {
+                panic!("{s} {v:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_type() {
+        let type_list = [
+            ("f", vec!['f']),
+            ("f,d", vec!['f', 'd']),
+            ("l,b,c,f", vec!['l', 'b', 'c', 'f']),
+            ("f,f", vec!['f']),
+            ("p,s", vec!['p', 's']),
+        ];
+        for x in &type_list {
+            match super::parse_type(x.0) {
+                Ok(v) => assert_eq!(v, x.1, "{}", x.0),
+                Err(e) => panic!("{e}"),
+            }
+        }
+
+        let invalid_list = ["", "x", "f,", "fd", "f,,d", "F"];
+        for s in &invalid_list {
+            if let Ok(v) = super::parse_type(s) {
+                panic!("{s} {v:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_test_entry() {
+        let d = crate::util::TempDir::new("filter");
+        std::fs::write(d.join("small"), "abc").unwrap();
+        std::fs::write(d.join("large"), "x".repeat(2000)).unwrap();
+        std::os::unix::fs::symlink("large", d.join("link")).unwrap();
+        let small = d.join("small");
+        let small = small.to_str().unwrap();
+        let large = d.join("large");
+        let large = large.to_str().unwrap();
+        let link = d.join("link");
+        let link = link.to_str().unwrap();
+        let dir = d.to_str().unwrap();
+
+        let fil = super::Filter::new();
+        assert!(fil.is_empty());
+        for f in [small, large, link, dir] {
+            assert!(!fil.test_entry(f, false).unwrap());
+        }
+
+        // size applies to regular files
+        let mut fil = super::Filter::new();
+        fil.min_size = Some(1000);
+        assert!(!fil.is_empty());
+        assert!(fil.test_entry(small, false).unwrap());
+        assert!(!fil.test_entry(large, false).unwrap());
+        assert!(!fil.test_entry(link, false).unwrap());
+        assert!(!fil.test_entry(dir, false).unwrap());
+        fil.min_size = None;
+        fil.max_size = Some(1000);
+        assert!(!fil.test_entry(small, false).unwrap());
+        assert!(fil.test_entry(large, false).unwrap());
+        assert!(fil.test_entry(link, true).unwrap());
+
+        // type of symlink depends on whether followed
+        let mut fil = super::Filter::new();
+        fil.type_list = vec![super::TYPE_REGULAR];
+        assert!(!fil.test_entry(small, false).unwrap());
+        assert!(fil.test_entry(link, false).unwrap());
+        assert!(!fil.test_entry(link, true).unwrap());
+        assert!(fil.test_entry(dir, false).unwrap());
+
+        // mtime applies to non directories
+        let mut fil = super::Filter::new();
+        fil.newer_than = Some(std::time::SystemTime::now() + std::time::Duration::from_secs(3600));
+        assert!(fil.test_entry(small, false).unwrap());
+        assert!(!fil.test_entry(dir, false).unwrap());
+        fil.newer_than = None;
+        fil.older_than = Some(std::time::UNIX_EPOCH);
+        assert!(fil.test_entry(small, false).unwrap());
+        fil.older_than = Some(std::time::SystemTime::now() + std::time::Duration::from_secs(3600));
+        assert!(!fil.test_entry(small, false).unwrap());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::cache;
+use std::os::unix::fs::MetadataExt;
+
+// hash values and written bytes
+type Sum = (Vec<Vec<u8>>, u64);
+
+// only regular files with multiple links are cached,
+// keyed as in cache so that modified inode is hashed again
+#[derive(Debug, Default)]
+pub(crate) struct Hardlink {
+    map: std::collections::HashMap<cache::Key, Sum>,
+}
+
+impl Hardlink {
+    pub(crate) fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn get_sum(&self, m: &std::fs::Metadata) -> Option<&Sum> {
+        if is_hardlink(m) {
+            self.map.get(&cache::get_key(m))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn insert_sum(&mut self, m: &std::fs::Metadata, sum: Vec<Vec<u8>>, written: u64) {
+        if is_hardlink(m) {
+            self.map.insert(cache::get_key(m), (sum, written));
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn num_inode(&self) -> usize {
+        self.map.len()
+    }
+}
+
+pub(crate) fn is_hardlink(m: &std::fs::Metadata) -> bool {
+    m.is_file() && m.nlink() > 1
+}
+
+pub(crate) fn get_nlink(m: &std::fs::Metadata) -> u64 {
+    m.nlink()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_sum() {
+        let d = crate::util::TempDir::new("hln");
+        std::fs::write(d.join("a"), "abc").unwrap();
+        std::fs::write(d.join("b"), "abc").unwrap();
+        let ma = std::fs::metadata(d.join("a")).unwrap();
+        let mb = std::fs::metadata(d.join("b")).unwrap();
+        assert!(!super::is_hardlink(&ma));
+        assert_eq!(super::get_nlink(&ma), 1);
+
+        // not cached unless hardlink
+        let mut hln = super::Hardlink::new();
+        hln.insert_sum(&ma, vec![vec![1]], 3);
+        assert!(hln.get_sum(&ma).is_none());
+        assert_eq!(hln.num_inode(), 0);
+
+        std::fs::hard_link(d.join("a"), d.join("c")).unwrap();
+        let ma = std::fs::metadata(d.join("a")).unwrap();
+        let mc = std::fs::metadata(d.join("c")).unwrap();
+        assert!(super::is_hardlink(&ma));
+        assert_eq!(super::get_nlink(&mc), 2);
+
+        hln.insert_sum(&ma, vec![vec![1]], 3);
+        assert_eq!(hln.get_sum(&mc), Some(&(vec![vec![1]], 3)));
+        assert!(hln.get_sum(&mb).is_none());
+        assert_eq!(hln.num_inode(), 1);
+
+        // modified via other link
+        std::fs::write(d.join("c"), "abcd").unwrap();
+        let mc = std::fs::metadata(d.join("c")).unwrap();
+        assert!(hln.get_sum(&mc).is_none());
+    }
+
+    #[cfg(feature = "io_uring")]
+    #[test]
+    fn test_read_ahead() {
+        // hashed once whether read ahead or not
+        let d = crate::util::TempDir::new("hln_uring");
+        std::fs::write(d.join("a"), "abc").unwrap();
+        std::fs::hard_link(d.join("a"), d.join("b")).unwrap();
+        std::fs::hard_link(d.join("a"), d.join("c")).unwrap();
+        std::fs::write(d.join("d"), "abc").unwrap();
+
+        for hashdeep in [false, true] {
+            let mut opt = crate::Opt {
+                backend: crate::backend::Backend::new(crate::backend::IO_URING, 4096, false)
+                    .unwrap(),
+                hashdeep,
+                hashdeep_algo: vec![crate::hash::SHA256.to_string()],
+                ..Default::default()
+            };
+            opt.hash_algo = crate::hash::SHA256.to_string();
+            let mut ctx = crate::dir::Context::new();
+            let mut sta = None;
+            let s = crate::util::capture_record(|| {
+                sta = Some(crate::dir::print_input(d.to_str().unwrap(), &mut ctx, &opt).unwrap());
+            });
+            let sta = sta.unwrap();
+            assert_eq!(sta.num_stat_regular(), 4, "{s}");
+            assert_eq!(sta.num_stat_hardlink(), 3, "{s}");
+            assert_eq!(sta.num_stat_reused(), 2, "{s}");
+            assert_eq!(ctx.hln.num_inode(), 1);
+        }
+    }
+}
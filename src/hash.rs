@@ -1,4 +1,8 @@
 use digest::Digest;
+use std::io::Seek;
+use std::os::unix::io::AsRawFd;
+
+use crate::backend;
 
 pub(crate) const MD5: &str = "md5";
 pub(crate) const SHA1: &str = "sha1";
@@ -12,16 +16,41 @@ pub(crate) const SHA3_224: &str = "sha3_224";
 pub(crate) const SHA3_256: &str = "sha3_256";
 pub(crate) const SHA3_384: &str = "sha3_384";
 pub(crate) const SHA3_512: &str = "sha3_512";
+pub(crate) const BLAKE3: &str = "blake3";
+
+// environment variable of hex encoded key unless key file specified
+pub(crate) const HMAC_KEY_ENV: &str = "DIRHASH_HMAC_KEY";
+
+// keyed BLAKE3 requires key of this size, other algorithms use HMAC
+pub(crate) const BLAKE3_KEY_SIZE: usize = 32;
+
+// set once before hashing, applies to every digest including squash
+static HMAC_KEY: std::sync::OnceLock<Vec<u8>> = std::sync::OnceLock::new();
 
 const BUFFER_SIZE: usize = 65536;
 
-pub(crate) fn get_available_hash_algo() -> [&'static str; 12] {
+static ZERO_BUFFER: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+
+pub(crate) fn get_available_hash_algo() -> [&'static str; 13] {
     [
         MD5, SHA1, SHA224, SHA256, SHA384, SHA512, SHA512_224, SHA512_256, SHA3_224, SHA3_256,
-        SHA3_384, SHA3_512,
+        SHA3_384, SHA3_512, BLAKE3,
     ]
 }
 
+pub(crate) fn set_hmac_key(key: Vec<u8>) -> std::io::Result<()> {
+    if key.is_empty() {
+        return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+    }
+    HMAC_KEY
+        .set(key)
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::AlreadyExists))
+}
+
+pub(crate) fn is_keyed() -> bool {
+    HMAC_KEY.get().is_some()
+}
+
 #[derive(Debug)]
 pub(crate) enum HashObj {
     MD5(md5::Md5),
@@ -36,9 +65,45 @@ pub(crate) enum HashObj {
     SHA3_256(sha3::Sha3_256),
     SHA3_384(sha3::Sha3_384),
     SHA3_512(sha3::Sha3_512),
+    BLAKE3(Box<blake3::Hasher>),
+    Hmac(Box<HashObj>, Box<HashObj>), // inner and outer
 }
 
+// keyed if key is set
 pub(crate) fn new_hash(hash_algo: &str) -> std::io::Result<HashObj> {
+    new_keyed_hash(hash_algo, HMAC_KEY.get().map(Vec::as_slice))
+}
+
+fn new_keyed_hash(hash_algo: &str, key: Option<&[u8]>) -> std::io::Result<HashObj> {
+    let Some(key) = key else {
+        return new_unkeyed_hash(hash_algo);
+    };
+    if hash_algo == BLAKE3 {
+        let Ok(key) = key.try_into() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        };
+        return Ok(HashObj::BLAKE3(Box::new(blake3::Hasher::new_keyed(key))));
+    }
+
+    // RFC 2104, key longer than block size is hashed first
+    let mut inner = new_unkeyed_hash(hash_algo)?;
+    let mut outer = new_unkeyed_hash(hash_algo)?;
+    let size = inner.block_size();
+    let mut k = if key.len() > size {
+        let mut h = new_unkeyed_hash(hash_algo)?;
+        h.update(key);
+        h.finalize()
+    } else {
+        key.to_vec()
+    };
+    k.resize(size, 0);
+    inner.update(&k.iter().map(|x| x ^ 0x36).collect::<Vec<u8>>());
+    outer.update(&k.iter().map(|x| x ^ 0x5c).collect::<Vec<u8>>());
+    Ok(HashObj::Hmac(Box::new(inner), Box::new(outer)))
+}
+
+// never keyed, e.g. squash internals and output file digest
+pub(crate) fn new_unkeyed_hash(hash_algo: &str) -> std::io::Result<HashObj> {
     Ok(match hash_algo {
         MD5 => HashObj::MD5(md5::Md5::new()),
         SHA1 => HashObj::SHA1(sha1::Sha1::new()),
@@ -52,13 +117,204 @@ pub(crate) fn new_hash(hash_algo: &str) -> std::io::Result<HashObj> {
         SHA3_256 => HashObj::SHA3_256(sha3::Sha3_256::new()),
         SHA3_384 => HashObj::SHA3_384(sha3::Sha3_384::new()),
         SHA3_512 => HashObj::SHA3_512(sha3::Sha3_512::new()),
+        BLAKE3 => HashObj::BLAKE3(Box::new(blake3::Hasher::new())),
         _ => return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
     })
 }
 
-pub(crate) fn get_file_hash(f: &str, hash_algo: &str) -> std::io::Result<(Vec<u8>, u64)> {
-    let mut r = std::io::BufReader::new(std::fs::File::open(f)?);
-    get_hash(&mut r, hash_algo)
+impl HashObj {
+    pub(crate) fn update(&mut self, b: &[u8]) {
+        match self {
+            HashObj::MD5(v) => v.update(b),
+            HashObj::SHA1(v) => v.update(b),
+            HashObj::SHA224(v) => v.update(b),
+            HashObj::SHA256(v) => v.update(b),
+            HashObj::SHA384(v) => v.update(b),
+            HashObj::SHA512(v) => v.update(b),
+            HashObj::SHA512_224(v) => v.update(b),
+            HashObj::SHA512_256(v) => v.update(b),
+            HashObj::SHA3_224(v) => v.update(b),
+            HashObj::SHA3_256(v) => v.update(b),
+            HashObj::SHA3_384(v) => v.update(b),
+            HashObj::SHA3_512(v) => v.update(b),
+            HashObj::BLAKE3(v) => {
+                v.update(b);
+            }
+            HashObj::Hmac(v, _) => v.update(b),
+        }
+    }
+
+    // input block size in bytes for HMAC
+    fn block_size(&self) -> usize {
+        match self {
+            HashObj::MD5(_)
+            | HashObj::SHA1(_)
+            | HashObj::SHA224(_)
+            | HashObj::SHA256(_)
+            | HashObj::BLAKE3(_) => 64,
+            HashObj::SHA384(_)
+            | HashObj::SHA512(_)
+            | HashObj::SHA512_224(_)
+            | HashObj::SHA512_256(_) => 128,
+            HashObj::SHA3_224(_) => 144,
+            HashObj::SHA3_256(_) => 136,
+            HashObj::SHA3_384(_) => 104,
+            HashObj::SHA3_512(_) => 72,
+            HashObj::Hmac(v, _) => v.block_size(),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        match self {
+            HashObj::MD5(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA1(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA224(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA256(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA384(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA512(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA512_224(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA512_256(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA3_224(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA3_256(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA3_384(v) => v.finalize()[..].to_vec(),
+            HashObj::SHA3_512(v) => v.finalize()[..].to_vec(),
+            HashObj::BLAKE3(v) => v.finalize().as_bytes().to_vec(),
+            HashObj::Hmac(v, mut w) => {
+                w.update(&v.finalize());
+                w.finalize()
+            }
+        }
+    }
+}
+
+pub(crate) fn get_file_hash(
+    f: &str,
+    hash_algo: &str,
+    bk: &backend::Backend,
+) -> std::io::Result<(Vec<u8>, u64)> {
+    let (mut bl, written, _) = get_file_hash_sparse(f, &[hash_algo.to_string()], bk)?;
+    Ok((bl.remove(0), written))
+}
+
+// hash values per algorithm, written bytes, and bytes in holes which were
+// hashed without being read
+pub(crate) type SparseSum = (Vec<Vec<u8>>, u64, u64);
+
+// hash file once with multiple algorithms
+pub(crate) fn get_file_hash_sparse(
+    f: &str,
+    hash_algo: &[String],
+    bk: &backend::Backend,
+) -> std::io::Result<SparseSum> {
+    let mut fp = bk.open_file(f)?;
+    bk.advise_start(&fp);
+    let ret = get_file_hash_impl(&mut fp, hash_algo, bk);
+    bk.advise_end(&fp);
+    ret
+}
+
+fn get_file_hash_impl(
+    fp: &mut std::fs::File,
+    hash_algo: &[String],
+    bk: &backend::Backend,
+) -> std::io::Result<SparseSum> {
+    let mut hl = new_hash_list(hash_algo)?;
+    let m = fp.metadata()?;
+    let mut buf = bk.alloc_buffer();
+
+    // holes aren't skipped with O_DIRECT, data offsets may be unaligned
+    let (written, hole) = if m.is_file() && bk.is_mmap(m.len()) {
+        (update_mmap_hash(fp, &mut hl, buf.len())?, 0)
+    } else if m.is_file() && !bk.is_direct() {
+        match update_sparse_hash(fp, m.len(), &mut hl, buf.as_mut_slice())? {
+            Some(v) => v,
+            None => (update_hash(fp, &mut hl, buf.as_mut_slice())?, 0),
+        }
+    } else {
+        (update_hash(fp, &mut hl, buf.as_mut_slice())?, 0)
+    };
+
+    Ok((
+        hl.into_iter().map(HashObj::finalize).collect(),
+        written,
+        hole,
+    ))
+}
+
+// file truncated while mapped raises SIGBUS, same as other mmap users
+fn update_mmap_hash(
+    fp: &std::fs::File,
+    hl: &mut [HashObj],
+    buffer_size: usize,
+) -> std::io::Result<u64> {
+    let mm = unsafe { memmap2::Mmap::map(fp)? };
+    for b in mm.chunks(buffer_size) {
+        for h in hl.iter_mut() {
+            h.update(b);
+        }
+    }
+    Ok(mm.len().try_into().unwrap())
+}
+
+// written bytes and bytes in holes, None if SEEK_DATA / SEEK_HOLE unsupported
+fn update_sparse_hash(
+    fp: &mut std::fs::File,
+    size: u64,
+    hl: &mut [HashObj],
+    buf: &mut [u8],
+) -> std::io::Result<Option<(u64, u64)>> {
+    let mut written = 0;
+    let mut hole = 0;
+
+    while written < size {
+        let pos = i64::try_from(written).unwrap();
+        let data = match lseek(fp, pos, libc::SEEK_DATA) {
+            Ok(v) => v,
+            Err(e) => match e.raw_os_error() {
+                // no data after pos, rest is a hole
+                Some(libc::ENXIO) => size,
+                Some(libc::EINVAL | libc::ENOTSUP) if written == 0 => return Ok(None),
+                _ => return Err(e),
+            },
+        };
+        let n = data.min(size) - written;
+        update_zero(hl, n);
+        written += n;
+        hole += n;
+        if written >= size {
+            break;
+        }
+
+        let next = lseek(fp, pos + i64::try_from(n).unwrap(), libc::SEEK_HOLE)?.min(size);
+        fp.seek(std::io::SeekFrom::Start(data))?;
+        let n = update_hash(&mut std::io::Read::take(&mut *fp, next - data), hl, buf)?;
+        written += n;
+        if n < next - data {
+            break; // truncated while reading
+        }
+    }
+    Ok(Some((written, hole)))
+}
+
+fn lseek(fp: &std::fs::File, offset: i64, whence: i32) -> std::io::Result<u64> {
+    let ret = unsafe { libc::lseek(fp.as_raw_fd(), offset, whence) };
+    if ret < 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(ret.try_into().unwrap())
+    }
+}
+
+// hash zeros without reading them
+fn update_zero(hl: &mut [HashObj], n: u64) {
+    let mut n = usize::try_from(n).unwrap();
+    while n > 0 {
+        let b = &ZERO_BUFFER[..n.min(BUFFER_SIZE)];
+        for h in hl.iter_mut() {
+            h.update(b);
+        }
+        n -= b.len();
+    }
 }
 
 pub(crate) fn get_byte_hash(s: &[u8], hash_algo: &str) -> std::io::Result<(Vec<u8>, u64)> {
@@ -83,12 +339,49 @@ pub(crate) fn get_hash(
     r: &mut impl std::io::BufRead,
     hash_algo: &str,
 ) -> std::io::Result<(Vec<u8>, u64)> {
-    let mut h = new_hash(hash_algo)?;
+    let (mut bl, written) = get_hash_multi(r, &[hash_algo.to_string()])?;
+    Ok((bl.remove(0), written))
+}
+
+pub(crate) fn get_hash_multi(
+    r: &mut impl std::io::BufRead,
+    hash_algo: &[String],
+) -> std::io::Result<(Vec<Vec<u8>>, u64)> {
+    let mut hl = new_hash_list(hash_algo)?;
+    let mut buf = vec![0; BUFFER_SIZE];
+    let written = update_hash(r, &mut hl, &mut buf)?;
+    Ok((hl.into_iter().map(HashObj::finalize).collect(), written))
+}
+
+// digest checked by external tools such as sha256sum -c
+pub(crate) fn get_unkeyed_hash(
+    r: &mut impl std::io::BufRead,
+    hash_algo: &str,
+) -> std::io::Result<(Vec<u8>, u64)> {
+    let mut hl = [new_unkeyed_hash(hash_algo)?];
+    let mut buf = vec![0; BUFFER_SIZE];
+    let written = update_hash(r, &mut hl, &mut buf)?;
+    let [h] = hl;
+    Ok((h.finalize(), written))
+}
+
+fn new_hash_list(hash_algo: &[String]) -> std::io::Result<Vec<HashObj>> {
+    let mut hl = vec![];
+    for s in hash_algo {
+        hl.push(new_hash(s)?);
+    }
+    Ok(hl)
+}
+
+fn update_hash(
+    r: &mut impl std::io::Read,
+    hl: &mut [HashObj],
+    buf: &mut [u8],
+) -> std::io::Result<u64> {
     let mut written = 0;
 
     loop {
-        let mut buf = [0; BUFFER_SIZE];
-        let ret = match r.read(&mut buf) {
+        let ret = match r.read(buf) {
             Ok(v) => match v {
                 0 => break,
                 _ => v,
@@ -97,39 +390,11 @@ pub(crate) fn get_hash(
         };
         let b = &buf[..ret];
         written += b.len();
-        match h {
-            HashObj::MD5(ref mut v) => v.update(b),
-            HashObj::SHA1(ref mut v) => v.update(b),
-            HashObj::SHA224(ref mut v) => v.update(b),
-            HashObj::SHA256(ref mut v) => v.update(b),
-            HashObj::SHA384(ref mut v) => v.update(b),
-            HashObj::SHA512(ref mut v) => v.update(b),
-            HashObj::SHA512_224(ref mut v) => v.update(b),
-            HashObj::SHA512_256(ref mut v) => v.update(b),
-            HashObj::SHA3_224(ref mut v) => v.update(b),
-            HashObj::SHA3_256(ref mut v) => v.update(b),
-            HashObj::SHA3_384(ref mut v) => v.update(b),
-            HashObj::SHA3_512(ref mut v) => v.update(b),
+        for h in hl.iter_mut() {
+            h.update(b);
         }
     }
-
-    Ok((
-        match h {
-            HashObj::MD5(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA1(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA224(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA256(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA384(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA512(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA512_224(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA512_256(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA3_224(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA3_256(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA3_384(v) => v.finalize()[..].to_vec(),
-            HashObj::SHA3_512(v) => v.finalize()[..].to_vec(),
-        },
-        written.try_into().unwrap(),
-    ))
+    Ok(written.try_into().unwrap())
 }
 
 pub(crate) fn get_hex_sum(sum: &[u8]) -> String {
@@ -154,6 +419,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_new_keyed_hash() {
+        let m = b"The quick brown fox jumps over the lazy dog";
+        let k = b"key".as_slice();
+        let long = [b'k'; 200];
+        let alg_key_sum_list = [
+            (super::MD5, k, "80070713463e7749b90c2dc24911e275"),
+            (super::SHA1, k, "de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9"),
+            (super::SHA224, k, "88ff8b54675d39b8f72322e65ff945c52d96379988ada25639747e69"),
+            (super::SHA256, k, "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"),
+            (super::SHA384, k, "d7f4727e2c0b39ae0f1e40cc96f60242d5b7801841cea6fc592c5d3e1ae50700582a96cf35e1e554995fe4e03381c237"),
+            (super::SHA512, k, "b42af09057bac1e2d41708e48a902e09b5ff7f12ab428a4fe86653c73dd248fb82f948a549f7b791a5b41915ee4d1ec3935357e4e2317250d0372afa2ebeeb3a"),
+            (super::SHA3_224, k, "ff6fa8447ce10fb1efdccfe62caf8b640fe46c4fb1007912bf85100f"),
+            (super::SHA3_256, k, "8c6e0683409427f8931711b10ca92a506eb1fafa48fadd66d76126f47ac2c333"),
+            (super::SHA3_384, k, "aa739ad9fcdf9be4a04f06680ade7a1bd1e01a0af64accb04366234cf9f6934a0f8589772f857681fcde8acc256091a2"),
+            (super::SHA3_512, k, "237a35049c40b3ef5ddd960b3dc893d8284953b9a4756611b1b61bffcf53edd979f93547db714b06ef0a692062c609b70208ab8d4a280ceee40ed8100f293063"),
+            (super::SHA256, &long, "86522d64cfd7d3d21199f47288e74cdc22a6395aff54f0270c7feef1d0fa61bf"),
+            (super::SHA3_512, &long, "0733f1f947887d6ad5deaca79b8e69ee625674ffa7cf7cc18c4ca7fbafe7efc44aa1f5ed233ce9af6b9252be19368e7247a938f694164e34f7311ca2830ad5a3"),
+        ];
+        for x in &alg_key_sum_list {
+            let mut h = super::new_keyed_hash(x.0, Some(x.1)).unwrap();
+            h.update(m);
+            assert_eq!(super::get_hex_sum(&h.finalize()), x.2, "{}", x.0);
+        }
+
+        // BLAKE3 test vectors of empty input
+        let h = super::new_keyed_hash(super::BLAKE3, None).unwrap();
+        assert_eq!(
+            super::get_hex_sum(&h.finalize()),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        let h = super::new_keyed_hash(super::BLAKE3, Some(b"whats the Elvish word for friend"))
+            .unwrap();
+        assert_eq!(
+            super::get_hex_sum(&h.finalize()),
+            "92b2b75604ed3c761f9d6f62392c8a9227ad0ea3f09573e783f1498a4ed60d26"
+        );
+        assert!(super::new_keyed_hash(super::BLAKE3, Some(k)).is_err());
+    }
+
     #[test]
     fn test_get_byte_hash() {
         let alg_sum_list_1 = [
@@ -233,4 +538,119 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_get_hash_multi() {
+        let hash_algo = [super::MD5.to_string(), super::SHA256.to_string()];
+        let s = "A".repeat(1_000_000);
+        let mut r = std::io::BufReader::new(s.as_bytes());
+        match super::get_hash_multi(&mut r, &hash_algo) {
+            Ok(v) => {
+                assert_eq!(v.0.len(), 2);
+                assert_eq!(
+                    super::get_hex_sum(&v.0[0]),
+                    "48fcdb8b87ce8ef779774199a856091d"
+                );
+                assert_eq!(
+                    super::get_hex_sum(&v.0[1]),
+                    "e23c0cda5bcdecddec446b54439995c7260c8cdcf2953eec9f5cdb6948e5898d"
+                );
+                assert_eq!(v.1, 1_000_000);
+            }
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn test_get_file_hash_sparse() {
+        let d = crate::util::TempDir::new("sparse");
+        let hash_algo = [super::SHA256.to_string()];
+
+        // hole, data, hole, data, hole
+        let f = d.join("sparse");
+        let fp = std::fs::File::create(&f).unwrap();
+        fp.set_len(10 * 1024 * 1024).unwrap();
+        std::os::unix::fs::FileExt::write_all_at(&fp, b"x", 3 * 1024 * 1024).unwrap();
+        std::os::unix::fs::FileExt::write_all_at(&fp, b"y", 7 * 1024 * 1024).unwrap();
+        drop(fp);
+
+        // empty file, and file of holes only
+        let e = d.join("empty");
+        std::fs::File::create(&e).unwrap();
+        let h = d.join("hole");
+        std::fs::File::create(&h).unwrap().set_len(12345).unwrap();
+
+        // holes reported as data if filesystem lacks SEEK_HOLE
+        let fp = std::fs::File::open(&f).unwrap();
+        let sparse = super::lseek(&fp, 0, libc::SEEK_HOLE).unwrap() < 10 * 1024 * 1024;
+
+        for f in [&f, &e, &h] {
+            let mut r = std::io::BufReader::new(std::fs::File::open(f).unwrap());
+            let (b, written) = super::get_hash_multi(&mut r, &hash_algo).unwrap();
+            match super::get_file_hash_sparse(
+                f.to_str().unwrap(),
+                &hash_algo,
+                &crate::backend::Backend::default(),
+            ) {
+                Ok(v) => {
+                    assert_eq!(v.0, b, "{f:?}");
+                    assert_eq!(v.1, written, "{f:?}");
+                    assert!(v.2 <= written, "{f:?}");
+                    if sparse && written > 0 {
+                        assert!(v.2 > 0, "{f:?}");
+                    }
+                }
+                Err(e) => panic!("{e}"),
+            }
+        }
+
+        if sparse {
+            let bk = crate::backend::Backend::default();
+            let (.., hole) =
+                super::get_file_hash_sparse(h.to_str().unwrap(), &hash_algo, &bk).unwrap();
+            assert_eq!(hole, 12345);
+            // two data bytes, each within an allocated extent
+            let (.., hole) =
+                super::get_file_hash_sparse(f.to_str().unwrap(), &hash_algo, &bk).unwrap();
+            assert!(hole <= 10 * 1024 * 1024 - 2, "{hole}");
+            assert!(hole >= 8 * 1024 * 1024, "{hole}");
+        }
+    }
+
+    #[test]
+    fn test_get_file_hash_backend() {
+        let d = crate::util::TempDir::new("backend");
+        let hash_algo = [super::MD5.to_string(), super::SHA256.to_string()];
+
+        // below and above mmap threshold, unaligned size
+        let s = "0123456789".repeat(300_001);
+        let file_list = [
+            ("empty", &s[..0]),
+            ("small", &s[..12345]),
+            ("large", &s[..]),
+        ];
+        for x in &file_list {
+            std::fs::write(d.join(x.0), x.1).unwrap();
+        }
+
+        for x in &file_list {
+            let f = d.join(x.0);
+            let mut r = std::io::BufReader::new(x.1.as_bytes());
+            let (b, written) = super::get_hash_multi(&mut r, &hash_algo).unwrap();
+            for name in &crate::backend::get_available_backend() {
+                for buffer_size in [1000, 4096, 1 << 20] {
+                    for fadvise in [false, true] {
+                        let bk = crate::backend::Backend::new(name, buffer_size, fadvise).unwrap();
+                        match super::get_file_hash_sparse(f.to_str().unwrap(), &hash_algo, &bk) {
+                            Ok(v) => {
+                                assert_eq!(v.0, b, "{x:?} {bk:?}");
+                                assert_eq!(v.1, written, "{x:?} {bk:?}");
+                            }
+                            Err(e) => panic!("{e}"),
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,398 @@
+use crate::hash;
+use crate::util;
+use crate::Opt;
+
+pub(crate) const HASHDEEP_HEADER: &str = "%%%% HASHDEEP-1.0";
+pub(crate) const HASHDEEP_SIZE: &str = "size";
+pub(crate) const HASHDEEP_FILENAME: &str = "filename";
+
+pub(crate) fn get_header_string(hash_algo: &[String]) -> String {
+    format!(
+        "%%%% {HASHDEEP_SIZE},{},{HASHDEEP_FILENAME}",
+        hash_algo.join(",")
+    )
+}
+
+pub(crate) fn print_header(
+    hash_algo: &[String],
+    args: &[String],
+    null: bool,
+) -> std::io::Result<()> {
+    util::print_record(HASHDEEP_HEADER, null);
+    util::print_record(&get_header_string(hash_algo), null);
+    util::print_record(
+        &format!("## Invoked from: {}", util::get_abspath(".")?),
+        null,
+    );
+    util::print_record(&format!("## $ {}", args.join(" ")), null);
+    util::print_record("##", null);
+    Ok(())
+}
+
+pub(crate) fn get_record_string(f: &str, size: u64, hex_sum: &[String]) -> String {
+    format!("{},{f}", get_record_key(size, hex_sum))
+}
+
+// size and digests without file path
+fn get_record_key(size: u64, hex_sum: &[String]) -> String {
+    format!("{size},{}", hex_sum.join(","))
+}
+
+#[derive(Debug)]
+struct Record {
+    f: String,
+    key: String,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Audit {
+    hash_algo: Vec<String>,
+    known: Vec<Record>,
+    known_path: std::collections::HashMap<String, usize>,
+    known_key: std::collections::HashMap<String, Vec<usize>>,
+    found: Vec<bool>,
+
+    examined: usize,
+    matched: Vec<String>,
+    moved: Vec<(String, String)>, // (path, known path)
+    new: Vec<String>,
+
+    collect: bool, // records are known rather than audited
+}
+
+impl Audit {
+    pub(crate) fn new(f: &str) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(f)?)
+    }
+
+    pub(crate) fn parse(s: &str) -> std::io::Result<Self> {
+        let mut audit = Self {
+            ..Default::default()
+        };
+        // NUL terminated if header is
+        let mut lines: Box<dyn Iterator<Item = &str>> =
+            if s.starts_with(&format!("{HASHDEEP_HEADER}\0")) {
+                Box::new(s.split('\0'))
+            } else {
+                Box::new(s.lines())
+            };
+
+        // first line is a fixed header
+        if lines.next() != Some(HASHDEEP_HEADER) {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+
+        // second line is "%%%% size,<algo>,...,filename"
+        let Some(x) = lines.next().and_then(|x| x.strip_prefix("%%%% ")) else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        };
+        let column: Vec<&str> = x.split(',').collect();
+        if column.len() < 3
+            || column[0] != HASHDEEP_SIZE
+            || column[column.len() - 1] != HASHDEEP_FILENAME
+        {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+        for x in &column[1..column.len() - 1] {
+            hash::new_hash(x)?;
+            audit.hash_algo.push((*x).to_string());
+        }
+
+        for x in lines {
+            if x.is_empty() || x.starts_with("##") {
+                continue;
+            }
+            // file path may contain ","
+            let v: Vec<&str> = x.splitn(column.len(), ',').collect();
+            if v.len() != column.len() {
+                return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+            }
+            let Ok(size) = v[0].parse::<u64>() else {
+                return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+            };
+            let mut hex_sum = vec![];
+            for s in &v[1..v.len() - 1] {
+                let (s, valid) = util::is_valid_hexsum(s);
+                if !valid {
+                    return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+                }
+                hex_sum.push(s.to_lowercase());
+            }
+            audit.append_known(v[v.len() - 1], &get_record_key(size, &hex_sum));
+        }
+        Ok(audit)
+    }
+
+    // known records collected from input walked first, e.g. diff
+    pub(crate) fn new_collect(hash_algo: &[String]) -> Self {
+        Self {
+            hash_algo: hash_algo.to_vec(),
+            collect: true,
+            ..Default::default()
+        }
+    }
+
+    // subsequent records are audited against collected records
+    pub(crate) fn finish_collect(&mut self) {
+        self.collect = false;
+    }
+
+    fn append_known(&mut self, f: &str, key: &str) {
+        let i = self.known.len();
+        self.known.push(Record {
+            f: f.to_string(),
+            key: key.to_string(),
+        });
+        self.known_path.insert(f.to_string(), i);
+        self.known_key.entry(key.to_string()).or_default().push(i);
+        self.found.push(false);
+    }
+
+    pub(crate) fn get_hash_algo(&self) -> &[String] {
+        &self.hash_algo
+    }
+
+    pub(crate) fn audit_record(&mut self, f: &str, size: u64, hex_sum: &[String]) {
+        let key = get_record_key(size, hex_sum);
+        if self.collect {
+            self.append_known(f, &key);
+            return;
+        }
+        self.examined += 1;
+
+        // same path with same digests
+        if let Some(&i) = self.known_path.get(f) {
+            if self.known[i].key == key {
+                self.found[i] = true;
+                self.matched.push(f.to_string());
+                return;
+            }
+        }
+
+        // same digests under different path, prefer the one not yet found
+        if let Some(l) = self.known_key.get(&key) {
+            let i = match l.iter().find(|&&i| !self.found[i]) {
+                Some(&i) => i,
+                None => l[0],
+            };
+            self.found[i] = true;
+            self.moved.push((f.to_string(), self.known[i].f.clone()));
+            return;
+        }
+
+        self.new.push(f.to_string());
+    }
+
+    pub(crate) fn num_examined(&self) -> usize {
+        self.examined
+    }
+
+    pub(crate) fn num_known(&self) -> usize {
+        self.known.len()
+    }
+
+    pub(crate) fn num_matched(&self) -> usize {
+        self.matched.len()
+    }
+
+    pub(crate) fn num_moved(&self) -> usize {
+        self.moved.len()
+    }
+
+    pub(crate) fn num_new(&self) -> usize {
+        self.new.len()
+    }
+
+    pub(crate) fn num_missing(&self) -> usize {
+        self.found.iter().filter(|x| !**x).count()
+    }
+
+    pub(crate) fn is_passed(&self) -> bool {
+        self.num_moved() == 0 && self.num_new() == 0 && self.num_missing() == 0
+    }
+
+    pub(crate) fn print_audit(&self, opt: &Opt) {
+        if opt.verbose {
+            for (f, k) in &self.moved {
+                println!("{f}: Moved from {k}");
+            }
+            for f in &self.new {
+                println!("{f}: New file");
+            }
+            for (i, x) in self.known.iter().enumerate() {
+                if !self.found[i] {
+                    println!("{}: Known file not found", x.f);
+                }
+            }
+        }
+        println!(
+            "Audit {}",
+            if self.is_passed() { "passed" } else { "failed" }
+        );
+        println!("{:>22}: {}", "Input files examined", self.num_examined());
+        println!("{:>22}: {}", "Known files expecting", self.num_known());
+        println!("{:>22}: {}", "Files matched", self.num_matched());
+        println!("{:>22}: {}", "Files moved", self.num_moved());
+        println!("{:>22}: {}", "New files found", self.num_new());
+        println!("{:>22}: {}", "Known files not found", self.num_missing());
+    }
+
+    // known path with other digests is changed rather than new and missing
+    pub(crate) fn get_diff(&self) -> Vec<(String, String)> {
+        let missing: std::collections::HashSet<&str> = self
+            .known
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.found[*i])
+            .map(|(_, x)| x.f.as_str())
+            .collect();
+        let mut l = vec![];
+        for f in &self.new {
+            if missing.contains(f.as_str()) {
+                l.push((f.clone(), "Changed".to_string()));
+            } else {
+                l.push((f.clone(), "Added".to_string()));
+            }
+        }
+        for (f, k) in &self.moved {
+            l.push((f.clone(), format!("Moved from {k}")));
+        }
+        let new: std::collections::HashSet<&str> = self.new.iter().map(String::as_str).collect();
+        for (i, x) in self.known.iter().enumerate() {
+            if !self.found[i] && !new.contains(x.f.as_str()) {
+                l.push((x.f.clone(), "Removed".to_string()));
+            }
+        }
+        l.sort();
+        l
+    }
+
+    pub(crate) fn print_diff(&self, opt: &Opt) {
+        for (f, s) in self.get_diff() {
+            util::print_record(&format!("{f}: {s}"), opt.null);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const KNOWN: &str = "%%%% HASHDEEP-1.0
+%%%% size,md5,sha256,filename
+## Invoked from: /tmp
+## $ hashdeep -r x
+##
+3,900150983cd24fb0d6963f7d28e17f72,ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad,x/abc
+1,0cc175b9c0f1b6a831c399e269772661,ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb,x/a,b
+";
+
+    #[test]
+    fn test_get_header_string() {
+        let hash_algo = ["md5".to_string(), "sha256".to_string()];
+        assert_eq!(
+            super::get_header_string(&hash_algo),
+            "%%%% size,md5,sha256,filename"
+        );
+    }
+
+    #[test]
+    fn test_get_record_string() {
+        let hex_sum = ["aa".to_string(), "bb".to_string()];
+        assert_eq!(super::get_record_string("x/y", 3, &hex_sum), "3,aa,bb,x/y");
+    }
+
+    #[test]
+    fn test_parse() {
+        let audit = match super::Audit::parse(KNOWN) {
+            Ok(v) => v,
+            Err(e) => panic!("{e}"),
+        };
+        assert_eq!(audit.get_hash_algo(), ["md5", "sha256"]);
+        assert_eq!(audit.num_known(), 2);
+        assert_eq!(audit.known[1].f, "x/a,b");
+
+        // NUL terminated, file path may contain newline
+        let s = KNOWN.replace('\n', "\0").replace("x/abc", "x/a\nbc");
+        let audit = match super::Audit::parse(&s) {
+            Ok(v) => v,
+            Err(e) => panic!("{e}"),
+        };
+        assert_eq!(audit.num_known(), 2);
+        assert_eq!(audit.known[0].f, "x/a\nbc");
+
+        let invalid_list = [
+            "",
+            "%%%% HASHDEEP-1.0\n",
+            "%%%% HASHDEEP-1.0\n%%%% size,xxx,filename\n",
+            "%%%% HASHDEEP-1.0\n%%%% md5,filename\n",
+            "%%%% HASHDEEP-1.0\n%%%% size,md5,filename\nx,0cc175b9c0f1b6a831c399e269772661,a\n",
+            "%%%% HASHDEEP-1.0\n%%%% size,md5,filename\n1,xxx,a\n",
+        ];
+        for s in &invalid_list {
+            assert!(super::Audit::parse(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_audit_record() {
+        let mut audit = match super::Audit::parse(KNOWN) {
+            Ok(v) => v,
+            Err(e) => panic!("{e}"),
+        };
+        let abc = [
+            "900150983cd24fb0d6963f7d28e17f72".to_string(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+        ];
+        let a = [
+            "0cc175b9c0f1b6a831c399e269772661".to_string(),
+            "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb".to_string(),
+        ];
+        audit.audit_record("x/abc", 3, &abc);
+        assert_eq!(audit.num_missing(), 1);
+        audit.audit_record("y/a", 1, &a);
+        audit.audit_record("x/new", 2, &abc);
+        assert_eq!(audit.num_examined(), 3);
+        assert_eq!(audit.num_matched(), 1);
+        assert_eq!(audit.num_moved(), 1);
+        assert_eq!(audit.num_new(), 1);
+        assert_eq!(audit.num_missing(), 0);
+        assert!(!audit.is_passed());
+    }
+
+    #[test]
+    fn test_get_diff() {
+        let hash_algo = ["md5".to_string()];
+        let x = ["900150983cd24fb0d6963f7d28e17f72".to_string()];
+        let y = ["0cc175b9c0f1b6a831c399e269772661".to_string()];
+        let z = ["d41d8cd98f00b204e9800998ecf8427e".to_string()];
+        let mut audit = super::Audit::new_collect(&hash_algo);
+        audit.audit_record("same", 3, &x);
+        audit.audit_record("changed", 1, &y);
+        audit.audit_record("old", 0, &z);
+        audit.audit_record("removed", 3, &y);
+        assert_eq!(audit.num_known(), 4);
+        assert_eq!(audit.num_examined(), 0);
+
+        audit.finish_collect();
+        audit.audit_record("same", 3, &x);
+        audit.audit_record("changed", 2, &y);
+        audit.audit_record("new", 0, &z);
+        audit.audit_record("added", 2, &z);
+        assert_eq!(
+            audit.get_diff(),
+            [
+                ("added".to_string(), "Added".to_string()),
+                ("changed".to_string(), "Changed".to_string()),
+                ("new".to_string(), "Moved from old".to_string()),
+                ("removed".to_string(), "Removed".to_string()),
+            ]
+        );
+
+        // identical
+        let mut audit = super::Audit::new_collect(&hash_algo);
+        audit.audit_record("same", 3, &x);
+        audit.finish_collect();
+        audit.audit_record("same", 3, &x);
+        assert!(audit.get_diff().is_empty());
+        assert!(audit.is_passed());
+    }
+}
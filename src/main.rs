@@ -1,7 +1,27 @@
+mod archive;
+mod backend;
+mod cache;
+mod chunk;
+mod cli;
+mod config;
+mod device;
 mod dir;
+mod dupes;
+mod filter;
+mod hardlink;
 mod hash;
+mod hashdeep;
+mod output;
+mod progress;
+mod serve;
+mod sign;
 mod stat;
+mod subsquash;
 mod util;
+mod watch;
+
+#[cfg(feature = "io_uring")]
+mod uring;
 
 // squash1
 #[cfg(feature = "squash1")]
@@ -33,6 +53,38 @@ struct Opt {
     swap: bool,
     sort: bool,
     squash: bool,
+    squash_depth: usize,
+    hashdeep: bool,
+    hashdeep_algo: Vec<String>,
+    audit: String,
+    diff: bool,
+    archive: bool,
+    strip_components: usize,
+    duplicates: bool,
+    duplicates_partial: bool,
+    hardlink: bool,
+    max_depth: usize,
+    min_depth: usize,
+    backend: backend::Backend,
+    filter: filter::Filter,
+    char_device: bool,
+    device_offset: u64,
+    device_length: Option<u64>,
+    device_number: bool,
+    special: bool,
+    chunk_size: u64,
+    chunk_merkle: bool,
+    chunk_verify: String,
+    progress: bool,
+    progress_scan: bool,
+    files_from: String,
+    null_input: bool,
+    output_file: String,
+    sign: String,
+    verify_signature: String,
+    hmac_key_file: String,
+    watch: bool,
+    null: bool,
     verbose: bool,
     debug: bool,
 }
@@ -52,6 +104,38 @@ impl Default for Opt {
             swap: false,
             sort: false,
             squash: false,
+            squash_depth: 0,
+            hashdeep: false,
+            hashdeep_algo: vec![],
+            audit: String::new(),
+            diff: false,
+            archive: false,
+            strip_components: 0,
+            duplicates: false,
+            duplicates_partial: false,
+            hardlink: false,
+            max_depth: usize::MAX,
+            min_depth: 0,
+            backend: backend::Backend::default(),
+            filter: filter::Filter::new(),
+            char_device: false,
+            device_offset: 0,
+            device_length: None,
+            device_number: false,
+            special: false,
+            chunk_size: 0,
+            chunk_merkle: false,
+            chunk_verify: String::new(),
+            progress: false,
+            progress_scan: false,
+            files_from: String::new(),
+            null_input: false,
+            output_file: String::new(),
+            sign: String::new(),
+            verify_signature: String::new(),
+            hmac_key_file: String::new(),
+            watch: false,
+            null: false,
             verbose: false,
             debug: false,
         }
@@ -66,69 +150,173 @@ fn print_version() {
     println!("{}", get_version_string());
 }
 
-fn usage(progname: &str, opts: &getopts::Options) {
-    print!(
-        "{}",
-        opts.usage(&format!("usage: {progname} [<options>] <paths>"))
-    );
+fn usage(progname: &str, cmd: Option<&str>) {
+    print!("{}", cli::get_usage(progname, cmd));
 }
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let progname = &args[0];
-
-    let mut opts = getopts::Options::new();
-    opts.optopt(
-        "",
-        "hash_algo",
-        "Hash algorithm to use (default \"sha256\")",
-        "<string>",
-    );
-    opts.optopt(
-        "",
-        "hash_verify",
-        "Message digest to verify in hex string",
-        "<string>",
-    );
-    opts.optflag("", "hash_only", "Do not print file paths");
-    opts.optflag("", "ignore_dot", "Ignore entries start with .");
-    opts.optflag("", "ignore_dot_dir", "Ignore directories start with .");
-    opts.optflag("", "ignore_dot_file", "Ignore files start with .");
-    opts.optflag("", "ignore_symlink", "Ignore symbolic links");
-    opts.optflag(
-        "",
-        "follow_symlink",
-        "Follow symbolic links unless directory",
-    );
-    opts.optflag("", "abs", "Print file paths in absolute path");
-    opts.optflag("", "swap", "Print file path first in each line");
-    opts.optflag("", "sort", "Print sorted file paths");
-    opts.optflag(
-        "",
-        "squash",
-        "Print squashed message digest instead of per file",
-    );
-    opts.optflag("", "verbose", "Enable verbose print");
-    opts.optflag("", "debug", "Enable debug print");
-    opts.optflag("v", "version", "Print version and exit");
-    opts.optflag("h", "help", "Print usage and exit");
-
-    let matches = match opts.parse(&args[1..]) {
+// parse options of command, or print usage and exit
+fn parse_args(progname: &str, cmd: &str, args: &[String]) -> cli::Matches {
+    let matches = match cli::Matches::parse(Some(cmd), args) {
         Ok(v) => v,
         Err(e) => {
             println!("{e}");
-            usage(progname, &opts);
+            usage(progname, Some(cmd));
+            std::process::exit(1);
+        }
+    };
+    if matches.opt_present("help") {
+        usage(progname, Some(cmd));
+        std::process::exit(0);
+    }
+    matches
+}
+
+fn serve_main(progname: &str, args: &[String]) {
+    let matches = parse_args(progname, cli::CMD_SERVE, args);
+    let Some(f) = matches.opt_str("socket") else {
+        usage(progname, Some(cli::CMD_SERVE));
+        std::process::exit(1);
+    };
+    if let Err(e) = serve::serve(&f) {
+        println!("Failed to serve {f}: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn client_main(progname: &str, args: &[String]) {
+    let matches = parse_args(progname, cli::CMD_CLIENT, args);
+    let (Some(sock), [op, f]) = (matches.opt_str("socket"), matches.free()) else {
+        usage(progname, Some(cli::CMD_CLIENT));
+        println!("Available op {:?}", serve::get_available_op());
+        std::process::exit(1);
+    };
+
+    // server may run in other directory
+    let f = match util::get_abspath(f) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Invalid path {f}: {e}");
+            std::process::exit(1);
+        }
+    };
+    let mut req = serde_json::json!({"op": op, "path": f});
+    if let Some(v) = matches.opt_str(serve::REQUEST_HASH_ALGO) {
+        req[serve::REQUEST_HASH_ALGO] = v.into();
+    }
+    if let Some(v) = matches.opt_str(serve::REQUEST_SQUASH_DEPTH) {
+        match v.parse::<u64>() {
+            Ok(n) => req[serve::REQUEST_SQUASH_DEPTH] = n.into(),
+            Err(e) => {
+                println!("Invalid squash depth {v}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(v) = matches.opt_str("manifest") {
+        match util::get_abspath(&v) {
+            Ok(x) => req["manifest"] = x.into(),
+            Err(e) => {
+                println!("Invalid manifest {v}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    for k in serve::REQUEST_BOOL {
+        if matches.opt_present(k) {
+            req[k] = true.into();
+        }
+    }
+
+    let res = match serve::request(&sock, &req) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Failed to request {sock}: {e}");
+            std::process::exit(1);
+        }
+    };
+    if res["ok"] != true {
+        println!("{}", res["error"].as_str().unwrap_or("unknown error"));
+        std::process::exit(1);
+    }
+    print!("{}", res["output"].as_str().unwrap_or_default());
+    if res["passed"] == false {
+        std::process::exit(1);
+    }
+}
+
+fn completion_main(progname: &str, args: &[String]) {
+    let matches = parse_args(progname, cli::CMD_COMPLETION, args);
+    let [shell] = matches.free() else {
+        usage(progname, Some(cli::CMD_COMPLETION));
+        std::process::exit(1);
+    };
+    // completion applies to command name rather than path invoked
+    let name = util::get_basename(progname).unwrap_or_else(|_| progname.to_string());
+    match cli::get_completion(&name, shell) {
+        Ok(v) => print!("{v}"),
+        Err(e) => {
+            println!("Invalid shell {shell}: {e}");
+            println!("Available shell {:?}", cli::get_available_shell());
             std::process::exit(1);
         }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let progname = &args[0];
+
+    // options without command are deprecated, and kept as is
+    match args.get(1).map(String::as_str) {
+        Some(cli::CMD_SERVE) => serve_main(progname, &args[2..]),
+        Some(cli::CMD_CLIENT) => client_main(progname, &args[2..]),
+        Some(cli::CMD_COMPLETION) => completion_main(progname, &args[2..]),
+        Some(x) if cli::is_command(x) => walk_main(&args, Some(x)),
+        _ => walk_main(&args, None),
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn walk_main(args: &[String], cmd: Option<&str>) {
+    let progname = &args[0];
+    let mut matches = if let Some(cmd) = cmd {
+        parse_args(progname, cmd, &args[2..])
+    } else {
+        match cli::Matches::parse(None, &args[1..]) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("{e}");
+                usage(progname, None);
+                std::process::exit(1);
+            }
+        }
     };
-    if matches.opt_present("v") {
+    if matches.opt_present("version") {
         print_version();
         std::process::exit(1);
     }
-    if matches.opt_present("h") {
-        usage(progname, &opts);
+    if matches.opt_present("help") {
+        usage(progname, None);
+        std::process::exit(1);
+    }
+
+    // options not specified fall back to config files and environment
+    let inp = if cmd == Some(cli::CMD_CHECK) {
+        matches.free().get(1)
+    } else {
+        matches.free().first()
+    };
+    let mut cfg = config::Config::new(cmd);
+    if let Err(e) = cfg.load(inp.map(String::as_str)) {
+        println!("Invalid config {e}");
         std::process::exit(1);
     }
+    // input may set options for itself, so always noticed
+    if let Some(f) = cfg.get_tree_config() {
+        eprintln!("Applied config {f}");
+    }
+    let loaded = cfg.get_loaded().to_vec();
+    matches.set_config(cfg);
 
     let mut opt = Opt {
         ..Default::default()
@@ -149,11 +337,206 @@ fn main() {
     opt.swap = matches.opt_present("swap");
     opt.sort = matches.opt_present("sort");
     opt.squash = matches.opt_present("squash");
+    if let Some(v) = matches.opt_str("squash_depth") {
+        opt.squash_depth = match v.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid squash depth {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    opt.hashdeep = matches.opt_present("hashdeep");
+    if let Some(v) = matches.opt_str("audit") {
+        opt.audit = v;
+        opt.hashdeep = true;
+    }
+    opt.archive = matches.opt_present("archive");
+    if let Some(v) = matches.opt_str("strip_components") {
+        opt.strip_components = match v.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid strip components {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    opt.duplicates = matches.opt_present("duplicates");
+    opt.duplicates_partial = matches.opt_present("duplicates_partial");
+    opt.hardlink = matches.opt_present("hardlink");
+    if let Some(v) = matches.opt_str("max_depth") {
+        opt.max_depth = match v.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid max depth {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(v) = matches.opt_str("min_depth") {
+        opt.min_depth = match v.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid min depth {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    let mut io_backend = opt.backend.name.clone();
+    if let Some(v) = matches.opt_str("io_backend") {
+        io_backend = v;
+    }
+    let mut buffer_size = opt.backend.buffer_size;
+    if let Some(v) = matches.opt_str("buffer_size") {
+        buffer_size = match v.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid buffer size {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    opt.backend =
+        match backend::Backend::new(&io_backend, buffer_size, matches.opt_present("fadvise")) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid I/O backend {io_backend} with buffer size {buffer_size}: {e}");
+                println!(
+                    "Available I/O backend {:?}",
+                    backend::get_available_backend()
+                );
+                std::process::exit(1);
+            }
+        };
+    if let Some(v) = matches.opt_str("min_size") {
+        opt.filter.min_size = match filter::parse_size(&v) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid min size {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(v) = matches.opt_str("max_size") {
+        opt.filter.max_size = match filter::parse_size(&v) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid max size {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(v) = matches.opt_str("newer_than") {
+        opt.filter.newer_than = match filter::parse_time(&v) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid newer than time {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(v) = matches.opt_str("older_than") {
+        opt.filter.older_than = match filter::parse_time(&v) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid older than time {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(v) = matches.opt_str("type") {
+        opt.filter.type_list = match filter::parse_type(&v) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid type {v}: {e}");
+                println!("Available type {:?}", filter::get_available_type());
+                std::process::exit(1);
+            }
+        };
+    }
+    opt.char_device = matches.opt_present("char_device");
+    if let Some(v) = matches.opt_str("device_offset") {
+        opt.device_offset = match filter::parse_size(&v) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid device offset {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    if let Some(v) = matches.opt_str("device_length") {
+        opt.device_length = match filter::parse_size(&v) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid device length {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    opt.device_number = matches.opt_present("device_number");
+    opt.special = matches.opt_present("special");
+    if let Some(v) = matches.opt_str("chunk_size") {
+        opt.chunk_size = match filter::parse_size(&v) {
+            Ok(0) => {
+                println!("Invalid chunk size {v}");
+                std::process::exit(1);
+            }
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid chunk size {v}: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+    opt.chunk_merkle = matches.opt_present("chunk_merkle");
+    if let Some(v) = matches.opt_str("chunk_verify") {
+        opt.chunk_verify = v;
+    }
+    opt.watch = matches.opt_present("watch");
+    opt.progress = matches.opt_present("progress");
+    opt.progress_scan = matches.opt_present("progress_scan");
+    if let Some(v) = matches.opt_str("files_from") {
+        opt.files_from = v;
+    }
+    opt.null_input = matches.opt_present("null_input");
+    opt.null = matches.opt_present("null");
+    if let Some(v) = matches.opt_str("output_file") {
+        opt.output_file = v;
+    }
+    if let Some(v) = matches.opt_str("hmac_key_file") {
+        opt.hmac_key_file = v;
+    }
+    if let Some(v) = matches.opt_str("sign") {
+        opt.sign = v;
+    }
+    if let Some(v) = matches.opt_str("verify_signature") {
+        opt.verify_signature = v;
+    }
     opt.verbose = matches.opt_present("verbose");
     opt.debug = matches.opt_present("debug");
 
-    if matches.free.is_empty() {
-        usage(progname, &opts);
+    // command implies options which are otherwise deprecated
+    let mut free = matches.free().to_vec();
+    match cmd {
+        Some(cli::CMD_SQUASH) => opt.squash = true,
+        Some(cli::CMD_DUPES) => opt.duplicates = true,
+        Some(cli::CMD_CHECK) if !free.is_empty() => {
+            opt.audit = free.remove(0);
+            opt.hashdeep = true;
+        }
+        Some(cli::CMD_DIFF) if free.len() == 2 => {
+            opt.diff = true;
+            opt.hashdeep = true;
+        }
+        Some(cli::CMD_CHECK | cli::CMD_DIFF) => {
+            usage(progname, cmd);
+            std::process::exit(1);
+        }
+        _ => (),
+    }
+
+    if free.is_empty() && opt.files_from.is_empty() {
+        usage(progname, cmd);
         std::process::exit(1);
     }
 
@@ -162,16 +545,170 @@ fn main() {
         std::process::exit(1);
     }
 
+    if !opt.sign.is_empty() && opt.output_file.is_empty() {
+        println!("--sign requires --output_file");
+        std::process::exit(1);
+    }
+    let key = if opt.sign.is_empty() {
+        None
+    } else {
+        match sign::SecretKey::new(&opt.sign) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid secret key {}: {e}", opt.sign);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // known file must be signed by public key if specified
+    if !opt.verify_signature.is_empty() {
+        if opt.audit.is_empty() {
+            println!("--verify_signature requires --audit");
+            std::process::exit(1);
+        }
+        let key = match sign::PublicKey::new(&opt.verify_signature) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Invalid public key {}: {e}", opt.verify_signature);
+                std::process::exit(1);
+            }
+        };
+        match sign::verify_file(&opt.audit, &key) {
+            Ok(true) => (),
+            Ok(false) => {
+                println!("Signature verification failed {}", opt.audit);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                println!(
+                    "Invalid signature {}{}: {e}",
+                    opt.audit,
+                    sign::SIGNATURE_SUFFIX
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // known file dictates hash algorithms to audit with
+    let mut ctx = dir::Context::new();
+    if !opt.audit.is_empty() {
+        match hashdeep::Audit::new(&opt.audit) {
+            Ok(v) => {
+                opt.hash_algo = v.get_hash_algo().join(",");
+                ctx.aud = Some(v);
+            }
+            Err(e) => {
+                println!("Invalid audit file {}: {e}", opt.audit);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if opt.hashdeep {
+        opt.hashdeep_algo = opt.hash_algo.split(',').map(str::to_string).collect();
+    } else {
+        opt.hashdeep_algo = vec![opt.hash_algo.clone()];
+    }
+    for x in &opt.hashdeep_algo {
+        if hash::new_hash(x).is_err() {
+            println!("Unsupported hash algorithm {x}");
+            println!(
+                "Available hash algorithm {:?}",
+                hash::get_available_hash_algo()
+            );
+            std::process::exit(1);
+        }
+    }
+
+    // first input is known to second input
+    if opt.diff {
+        ctx.aud = Some(hashdeep::Audit::new_collect(&opt.hashdeep_algo));
+    }
+
+    // key never comes from command line
+    let hmac_key = if !opt.hmac_key_file.is_empty() {
+        match std::fs::read(&opt.hmac_key_file) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid HMAC key file {}: {e}", opt.hmac_key_file);
+                std::process::exit(1);
+            }
+        }
+    } else if let Ok(v) = std::env::var(hash::HMAC_KEY_ENV) {
+        match hex::decode(v.trim()) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid {}: {e}", hash::HMAC_KEY_ENV);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    if let Some(v) = hmac_key {
+        if opt.hashdeep_algo.iter().any(|x| x == hash::BLAKE3) && v.len() != hash::BLAKE3_KEY_SIZE {
+            println!(
+                "Invalid HMAC key size {} for {}, expected {}",
+                v.len(),
+                hash::BLAKE3,
+                hash::BLAKE3_KEY_SIZE
+            );
+            std::process::exit(1);
+        }
+        if let Err(e) = hash::set_hmac_key(v) {
+            println!("Invalid HMAC key: {e}");
+            std::process::exit(1);
+        }
+    }
+
     if opt.verbose {
-        println!("{}", opt.hash_algo);
+        for x in &loaded {
+            println!("Loaded config {x}");
+        }
+        println!("{opt:#?}");
+        if hash::is_keyed() {
+            println!("{} (keyed)", opt.hash_algo);
+        } else {
+            println!("{}", opt.hash_algo);
+        }
+    }
+
+    if opt.squash_depth > 0 && !opt.squash {
+        println!("--squash_depth requires --squash");
+        std::process::exit(1);
+    }
+
+    if opt.hashdeep && opt.squash {
+        println!("--hashdeep and --squash are exclusive");
+        std::process::exit(1);
+    }
+
+    if opt.duplicates && (opt.squash || opt.hashdeep || opt.archive) {
+        println!("--duplicates and --squash, --hashdeep or --archive are exclusive");
+        std::process::exit(1);
+    }
+
+    if opt.watch
+        && (opt.duplicates || opt.archive || !opt.audit.is_empty() || !opt.output_file.is_empty())
+    {
+        println!("--watch and --duplicates, --archive, --audit or --output_file are exclusive");
+        std::process::exit(1);
+    }
+
+    if (opt.chunk_merkle || !opt.chunk_verify.is_empty()) && opt.chunk_size == 0 {
+        println!("--chunk_merkle and --chunk_verify require --chunk_size");
+        std::process::exit(1);
+    }
+
+    if opt.chunk_merkle && !opt.chunk_verify.is_empty() {
+        println!("--chunk_merkle and --chunk_verify are exclusive");
+        std::process::exit(1);
     }
 
-    if hash::new_hash(&opt.hash_algo).is_err() {
-        println!("Unsupported hash algorithm {}", opt.hash_algo);
-        println!(
-            "Available hash algorithm {:?}",
-            hash::get_available_hash_algo()
-        );
+    if opt.chunk_size > 0 && (opt.squash || opt.hashdeep || opt.duplicates) {
+        println!("--chunk_size and --squash, --hashdeep or --duplicates are exclusive");
         std::process::exit(1);
     }
 
@@ -196,13 +733,142 @@ fn main() {
         std::process::exit(1);
     }
 
-    let args = matches.free;
-    for (i, x) in args.iter().enumerate() {
-        if let Err(e) = dir::print_input(x, &opt) {
+    if opt.duplicates {
+        ctx.dup = Some(dupes::Dupes::new());
+    }
+
+    if !opt.chunk_verify.is_empty() {
+        match chunk::Chunk::new(&opt.chunk_verify, opt.chunk_size, opt.swap, opt.null) {
+            Ok(v) => ctx.chk = Some(v),
+            Err(e) => {
+                println!("Invalid chunk file {}: {e}", opt.chunk_verify);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // changes are hashed against files hashed in initial walk
+    if opt.watch {
+        ctx.cac = Some(cache::Cache::new());
+    }
+
+    let mut input_list = free;
+    if !opt.files_from.is_empty() {
+        match util::read_path_list(&opt.files_from, opt.null_input) {
+            Ok(v) => input_list.extend(v),
+            Err(e) => {
+                println!("Invalid files_from {}: {e}", opt.files_from);
+                std::process::exit(1);
+            }
+        }
+    }
+    if opt.progress && progress::is_available() {
+        let total = if opt.progress_scan {
+            Some(progress::scan_input(&input_list, opt.follow_symlink))
+        } else {
+            None
+        };
+        ctx.pro = Some(progress::Progress::new(total));
+    }
+
+    // subsequent records go to output file if specified
+    let out = if opt.output_file.is_empty() {
+        None
+    } else {
+        match output::Output::new(&opt.output_file) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                println!("Invalid output file {}: {e}", opt.output_file);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    if opt.hashdeep && ctx.aud.is_none() {
+        if let Err(e) = hashdeep::print_header(&opt.hashdeep_algo, args, opt.null) {
+            panic!("{e}");
+        }
+    }
+
+    // stat of each input kept for watch
+    let mut sta_list = vec![];
+    if opt.squash && !opt.files_from.is_empty() {
+        if let Err(e) = dir::print_input_list(&input_list, &mut ctx, &opt) {
+            panic!("{e}");
+        }
+    } else {
+        for (i, x) in input_list.iter().enumerate() {
+            match dir::print_input(x, &mut ctx, &opt) {
+                Ok(v) => sta_list.push(v),
+                Err(e) => panic!("{e}"),
+            }
+            if opt.diff && i == 0 {
+                if let Some(v) = &mut ctx.aud {
+                    v.finish_collect();
+                }
+            }
+            if opt.verbose && i != input_list.len() - 1 {
+                println!();
+            }
+        }
+    }
+
+    if let Some(v) = &ctx.pro {
+        v.finish();
+    }
+
+    if opt.watch {
+        ctx.pro = None;
+        if let Err(e) = watch::watch_input(&input_list, sta_list, &mut ctx, &opt) {
+            panic!("{e}");
+        }
+    }
+
+    if let Some(v) = &mut ctx.dup {
+        if opt.verbose {
+            println!();
+        }
+        if let Err(e) = v.print_duplicates(&opt) {
             panic!("{e}");
         }
-        if opt.verbose && i != args.len() - 1 {
+    }
+
+    if let Some(v) = &mut ctx.chk {
+        v.print_unseen(opt.null);
+    }
+
+    if let Some(v) = &ctx.aud {
+        if opt.verbose {
             println!();
         }
+        if opt.diff {
+            v.print_diff(&opt);
+        } else {
+            v.print_audit(&opt);
+        }
+    }
+
+    if let Some(v) = out {
+        if let Err(e) = v.finish() {
+            panic!("{e}");
+        }
+    }
+
+    if let Some(v) = &key {
+        if let Err(e) = sign::sign_file(&opt.output_file, v) {
+            panic!("{e}");
+        }
+    }
+
+    if let Some(v) = &ctx.aud {
+        if !v.is_passed() {
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(v) = &ctx.chk {
+        if !v.is_passed() {
+            std::process::exit(1);
+        }
     }
 }
@@ -0,0 +1,197 @@
+use crate::hash;
+use crate::util;
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::os::fd::FromRawFd;
+use std::os::unix::io::AsRawFd;
+
+pub(crate) const DIGEST_SUFFIX: &str = ".sha256";
+
+// records written to temp file in same directory as output file,
+// renamed to output file on finish so it is never partially written,
+// other prints such as stat stay on stdout
+#[derive(Debug)]
+pub(crate) struct Output {
+    f: String,
+    tmp: String,
+    fp: std::fs::File,
+    active: bool, // records written to temp file until finish
+}
+
+impl Output {
+    pub(crate) fn new(f: &str) -> std::io::Result<Self> {
+        let f = util::get_abspath(f)?;
+        let tmp = get_temp_path(&f)?;
+        let fp = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)?;
+        let w = std::io::BufWriter::new(fp.try_clone()?);
+        util::set_record_writer(Some(Box::new(w)));
+        Ok(Self {
+            f,
+            tmp,
+            fp,
+            active: true,
+        })
+    }
+
+    // flush records, rename temp file, then write digest of output file
+    pub(crate) fn finish(mut self) -> std::io::Result<()> {
+        let Some(mut w) = util::set_record_writer(None) else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        };
+        self.active = false;
+        w.flush()?;
+        drop(w);
+        self.fp.sync_all()?;
+
+        // unkeyed even with --hmac_key_file
+        let mut r = std::io::BufReader::new(std::fs::File::open(&self.tmp)?);
+        let (b, _) = hash::get_unkeyed_hash(&mut r, hash::SHA256)?;
+        std::fs::rename(&self.tmp, &self.f)?;
+
+        // compatible with sha256sum -c in output file directory
+        let s = util::get_xsum_format_string(
+            &util::get_basename(&self.f)?,
+            &hash::get_hex_sum(&b),
+            false,
+        );
+        write_file(&format!("{}{DIGEST_SUFFIX}", self.f), &format!("{s}\n"))
+    }
+}
+
+impl Drop for Output {
+    // e.g. panic before finish, leave output file as is
+    fn drop(&mut self) {
+        if self.active {
+            util::set_record_writer(None);
+            let _ = std::fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+// stdout redirected to anonymous memory file until finish
+#[derive(Debug)]
+pub(crate) struct Capture {
+    fp: std::fs::File,
+    stdout: Option<std::fs::File>, // original stdout until finish
+}
+
+impl Capture {
+    pub(crate) fn new() -> std::io::Result<Self> {
+        let fd = unsafe { libc::memfd_create(c"dirhash-capture".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fp = std::fs::File::from(unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) });
+        let stdout = redirect_stdout(&fp)?;
+        Ok(Self {
+            fp,
+            stdout: Some(stdout),
+        })
+    }
+
+    // restore stdout and return what was printed
+    pub(crate) fn finish(mut self) -> std::io::Result<Vec<u8>> {
+        let Some(stdout) = self.stdout.take() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        };
+        redirect_stdout(&stdout)?;
+        let mut b = vec![];
+        std::io::Seek::rewind(&mut self.fp)?;
+        std::io::Read::read_to_end(&mut self.fp, &mut b)?;
+        Ok(b)
+    }
+}
+
+impl Drop for Capture {
+    // e.g. panic before finish
+    fn drop(&mut self) {
+        if let Some(stdout) = self.stdout.take() {
+            let _ = redirect_stdout(&stdout);
+        }
+    }
+}
+
+fn get_temp_path(f: &str) -> std::io::Result<String> {
+    Ok(format!(
+        "{}/.{}.{}.tmp",
+        util::get_dirpath(f)?,
+        util::get_basename(f)?,
+        std::process::id()
+    ))
+}
+
+// returns duplicate of stdout before redirect
+fn redirect_stdout(fp: &std::fs::File) -> std::io::Result<std::fs::File> {
+    std::io::stdout().flush()?;
+    let old = std::fs::File::from(std::io::stdout().as_fd().try_clone_to_owned()?);
+    if unsafe { libc::dup2(fp.as_raw_fd(), std::io::stdout().as_raw_fd()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(old)
+}
+
+// atomically via temp file, e.g. digest or signature of output file
+pub(crate) fn write_file(f: &str, s: &str) -> std::io::Result<()> {
+    let tmp = get_temp_path(f)?;
+    let mut fp = std::fs::File::create(&tmp)?;
+    fp.write_all(s.as_bytes())?;
+    fp.sync_all()?;
+    std::fs::rename(&tmp, f)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_temp_path() {
+        let pid = std::process::id();
+        assert_eq!(
+            super::get_temp_path("/a/b").unwrap(),
+            format!("/a/.b.{pid}.tmp")
+        );
+        assert!(super::get_temp_path("/").is_err());
+    }
+
+    #[test]
+    fn test_output() {
+        let d = crate::util::TempDir::new("output-verbose");
+        std::fs::write(d.join("a"), "abc").unwrap();
+        let f = d.join("out");
+        let out = super::Output::new(f.to_str().unwrap()).unwrap();
+        let opt = crate::Opt {
+            verbose: true,
+            ..Default::default()
+        };
+        crate::dir::print_input(
+            d.join("a").to_str().unwrap(),
+            &mut crate::dir::Context::new(),
+            &opt,
+        )
+        .unwrap();
+        out.finish().unwrap();
+
+        // stat printed with --verbose is not in output file
+        let s = std::fs::read_to_string(&f).unwrap();
+        let h = crate::hash::get_hex_sum(
+            &crate::hash::get_byte_hash(b"abc", crate::hash::SHA256)
+                .unwrap()
+                .0,
+        );
+        assert_eq!(s, format!("{h}  a\n"));
+        let s = std::fs::read_to_string(format!("{}.sha256", f.to_str().unwrap())).unwrap();
+        assert!(s.ends_with("  out\n"));
+    }
+
+    #[test]
+    fn test_write_file() {
+        let d = crate::util::TempDir::new("output");
+        let f = d.join("x");
+        let f = f.to_str().unwrap();
+        std::fs::write(f, "old").unwrap();
+        super::write_file(f, "new\n").unwrap();
+        assert_eq!(std::fs::read_to_string(f).unwrap(), "new\n");
+        assert_eq!(std::fs::read_dir(&d).unwrap().count(), 1);
+    }
+}
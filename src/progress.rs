@@ -0,0 +1,217 @@
+use std::io::IsTerminal;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+
+// redraw status line at most this often
+const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+const DEFAULT_WIDTH: usize = 80;
+
+// status line drawn on terminal shared with stdout
+static DRAWN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// status line goes to stderr, stdout may be piped
+pub(crate) fn is_available() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+#[derive(Debug)]
+pub(crate) struct Progress {
+    start: std::time::Instant,
+    last: Option<std::time::Instant>,
+    num_file: usize,
+    num_byte: u64,
+    total_byte: Option<u64>, // pre-scanned if specified
+    path: String,
+    shared: bool, // stdout is also terminal
+}
+
+impl Progress {
+    pub(crate) fn new(total_byte: Option<u64>) -> Self {
+        Self {
+            start: std::time::Instant::now(),
+            last: None,
+            num_file: 0,
+            num_byte: 0,
+            total_byte,
+            path: String::new(),
+            shared: std::io::stdout().is_terminal(),
+        }
+    }
+
+    pub(crate) fn set_path(&mut self, f: &str) {
+        f.clone_into(&mut self.path);
+        self.refresh();
+    }
+
+    pub(crate) fn append_file(&mut self, written: u64) {
+        self.num_file += 1;
+        self.num_byte += written;
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        let now = std::time::Instant::now();
+        if let Some(t) = self.last {
+            if now.duration_since(t) < REFRESH_INTERVAL {
+                return;
+            }
+        }
+        self.last = Some(now);
+        let s = self.get_status_string(now.duration_since(self.start), get_width());
+        let mut e = std::io::stderr().lock();
+        let _ = write!(e, "\r{s}\x1b[K");
+        let _ = e.flush();
+        if self.shared {
+            DRAWN.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    // clear status line
+    pub(crate) fn finish(&self) {
+        if self.last.is_some() {
+            eprint!("\r\x1b[K");
+        }
+        DRAWN.store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn get_status_string(&self, elapsed: std::time::Duration, width: usize) -> String {
+        let sec = elapsed.as_secs_f64();
+        let rate = if sec > 0.0 {
+            self.num_byte as f64 / sec
+        } else {
+            0.0
+        };
+        let mut s = format!(
+            "{} files, {:.1} MiB, {:.1} MB/s",
+            self.num_file,
+            self.num_byte as f64 / (1 << 20) as f64,
+            rate / 1_000_000.0
+        );
+
+        // pre-scanned total may be off as entries change or get ignored
+        if let Some(total) = self.total_byte {
+            let remain = total.saturating_sub(self.num_byte);
+            let pct = (self.num_byte.min(total) * 100)
+                .checked_div(total)
+                .unwrap_or(100);
+            s += &format!(", {pct}%");
+            if rate > 0.0 {
+                let eta = (remain as f64 / rate) as u64;
+                s += &format!(", ETA {}:{:02}:{:02}", eta / 3600, eta / 60 % 60, eta % 60);
+            }
+        }
+
+        // keep tail of path within terminal width
+        if !self.path.is_empty() {
+            let n = width.saturating_sub(s.chars().count() + 3);
+            let c = self.path.chars().count();
+            if c <= n {
+                s += &format!("  {}", self.path);
+            } else if n > 3 {
+                let v: String = self.path.chars().skip(c - (n - 3)).collect();
+                s += &format!("  ...{v}");
+            }
+        }
+        s
+    }
+}
+
+// clear status line before stdout output, redrawn on next refresh
+pub(crate) fn clear_line() {
+    if DRAWN.swap(false, std::sync::atomic::Ordering::Relaxed) {
+        eprint!("\r\x1b[K");
+    }
+}
+
+fn get_width() -> usize {
+    let mut ws = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let ret = unsafe { libc::ioctl(std::io::stderr().as_raw_fd(), libc::TIOCGWINSZ, &raw mut ws) };
+    if ret == 0 && ws.ws_col > 0 {
+        usize::from(ws.ws_col)
+    } else {
+        DEFAULT_WIDTH
+    }
+}
+
+// total regular file bytes under inputs, errors ignored as this is an estimate
+pub(crate) fn scan_input(l: &[String], follow_symlink: bool) -> u64 {
+    let mut total = 0;
+    for f in l {
+        for entry in walkdir::WalkDir::new(f)
+            .into_iter()
+            .filter_map(std::result::Result::ok)
+        {
+            let m = if entry.path_is_symlink() && follow_symlink {
+                std::fs::metadata(entry.path())
+            } else {
+                std::fs::symlink_metadata(entry.path())
+            };
+            if let Ok(m) = m {
+                if m.is_file() {
+                    total += m.len();
+                }
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_status_string() {
+        let mut p = super::Progress::new(None);
+        p.num_file = 3;
+        p.num_byte = 10 << 20;
+        let d = std::time::Duration::from_secs(2);
+        assert_eq!(p.get_status_string(d, 80), "3 files, 10.0 MiB, 5.2 MB/s");
+
+        p.total_byte = Some(40 << 20);
+        assert_eq!(
+            p.get_status_string(d, 80),
+            "3 files, 10.0 MiB, 5.2 MB/s, 25%, ETA 0:00:06"
+        );
+
+        p.path = "/path/to/file".to_string();
+        assert_eq!(
+            p.get_status_string(d, 80),
+            "3 files, 10.0 MiB, 5.2 MB/s, 25%, ETA 0:00:06  /path/to/file"
+        );
+        assert_eq!(
+            p.get_status_string(d, 56),
+            "3 files, 10.0 MiB, 5.2 MB/s, 25%, ETA 0:00:06  .../file"
+        );
+        assert_eq!(
+            p.get_status_string(d, 40),
+            "3 files, 10.0 MiB, 5.2 MB/s, 25%, ETA 0:00:06"
+        );
+
+        // nothing hashed yet
+        let p = super::Progress::new(Some(0));
+        assert_eq!(
+            p.get_status_string(std::time::Duration::ZERO, 80),
+            "0 files, 0.0 MiB, 0.0 MB/s, 100%"
+        );
+    }
+
+    #[test]
+    fn test_scan_input() {
+        let d = crate::util::TempDir::new("progress");
+        std::fs::create_dir_all(d.join("x")).unwrap();
+        std::fs::write(d.join("a"), "abc").unwrap();
+        std::fs::write(d.join("x/b"), "abcdefg").unwrap();
+        std::os::unix::fs::symlink("a", d.join("l")).unwrap();
+
+        let l = [d.to_str().unwrap().to_string()];
+        assert_eq!(super::scan_input(&l, false), 10);
+        assert_eq!(super::scan_input(&l, true), 13);
+        let l = [d.join("a").to_str().unwrap().to_string()];
+        assert_eq!(super::scan_input(&l, false), 3);
+    }
+}
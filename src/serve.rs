@@ -0,0 +1,288 @@
+use crate::cache;
+use crate::dir;
+use crate::hash;
+use crate::hashdeep;
+use crate::output;
+use crate::Opt;
+use std::io::BufRead;
+use std::io::Write;
+use std::os::unix::fs::FileTypeExt;
+
+pub(crate) const OP_HASH: &str = "hash";
+pub(crate) const OP_SQUASH: &str = "squash";
+pub(crate) const OP_VERIFY: &str = "verify";
+
+pub(crate) fn get_available_op() -> [&'static str; 3] {
+    [OP_HASH, OP_SQUASH, OP_VERIFY]
+}
+
+// request fields other than op, path and manifest, mapped to options
+pub(crate) const REQUEST_BOOL: [&str; 11] = [
+    "hash_only",
+    "ignore_dot",
+    "ignore_dot_dir",
+    "ignore_dot_file",
+    "ignore_symlink",
+    "follow_symlink",
+    "abs",
+    "swap",
+    "sort",
+    "hashdeep",
+    "hardlink",
+];
+pub(crate) const REQUEST_HASH_ALGO: &str = "hash_algo";
+pub(crate) const REQUEST_SQUASH_DEPTH: &str = "squash_depth";
+
+// idle client is disconnected so that others are not kept waiting
+const READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// one JSON request per line, answered by one JSON response per line
+// requests are handled one at a time as output is captured from stdout,
+// hash values of unchanged files are kept across requests
+pub(crate) fn serve(f: &str) -> std::io::Result<()> {
+    let listener = bind(f)?;
+    let mut cac = Some(cache::Cache::new());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{e}");
+                continue;
+            }
+        };
+        if let Err(e) = handle_client(stream, &mut cac) {
+            eprintln!("{e}");
+        }
+    }
+    Ok(())
+}
+
+// socket left by server no longer running is replaced
+fn bind(f: &str) -> std::io::Result<std::os::unix::net::UnixListener> {
+    if std::fs::symlink_metadata(f).is_ok_and(|m| m.file_type().is_socket())
+        && std::os::unix::net::UnixStream::connect(f).is_err()
+    {
+        std::fs::remove_file(f)?;
+    }
+    // socket created with 0600 as it is never accessible to others
+    let mask = unsafe { libc::umask(0o177) };
+    let ret = std::os::unix::net::UnixListener::bind(f);
+    unsafe { libc::umask(mask) };
+    ret
+}
+
+fn handle_client(
+    stream: std::os::unix::net::UnixStream,
+    cac: &mut Option<cache::Cache>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let r = std::io::BufReader::new(stream.try_clone()?);
+    let mut w = stream;
+    for line in r.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let res = match serde_json::from_str(&line) {
+            Ok(v) => match handle_request(&v, cac) {
+                Ok(v) => v,
+                Err(e) => get_error_response(&e),
+            },
+            Err(e) => get_error_response(&e.into()),
+        };
+        writeln!(w, "{res}")?;
+    }
+    Ok(())
+}
+
+fn get_error_response(e: &std::io::Error) -> serde_json::Value {
+    serde_json::json!({"ok": false, "error": e.to_string()})
+}
+
+fn handle_request(
+    req: &serde_json::Value,
+    cac: &mut Option<cache::Cache>,
+) -> std::io::Result<serde_json::Value> {
+    let op = get_request_str(req, "op")?;
+    let f = get_request_str(req, "path")?;
+    let mut opt = get_request_opt(req)?;
+    opt.squash = op == OP_SQUASH;
+
+    let aud = match op {
+        OP_HASH | OP_SQUASH => None,
+        OP_VERIFY => {
+            let aud = hashdeep::Audit::new(get_request_str(req, "manifest")?)?;
+            opt.hash_algo = aud.get_hash_algo().join(",");
+            opt.hashdeep = true;
+            Some(aud)
+        }
+        _ => return Err(get_invalid_error(&format!("op {op}"))),
+    };
+    if opt.hashdeep {
+        if opt.squash {
+            return Err(get_invalid_error("hashdeep with squash"));
+        }
+        opt.hashdeep_algo = opt.hash_algo.split(',').map(str::to_string).collect();
+    } else {
+        opt.hashdeep_algo = vec![opt.hash_algo.clone()];
+    }
+    for x in &opt.hashdeep_algo {
+        if hash::new_hash(x).is_err() {
+            return Err(get_invalid_error(&format!("hash algorithm {x}")));
+        }
+    }
+
+    // hardlink cache is not validated against file changes unlike cache
+    let mut ctx = dir::Context::new();
+    ctx.aud = aud;
+    ctx.cac = cac.take();
+
+    let cap = match output::Capture::new() {
+        Ok(v) => v,
+        Err(e) => {
+            *cac = ctx.cac.take();
+            return Err(e);
+        }
+    };
+    // a panic only fails this request
+    let ret = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        handle_op(f, &mut ctx, &opt)
+    }));
+    *cac = ctx.cac.take();
+    let b = cap.finish()?;
+    match ret {
+        Ok(v) => v?,
+        Err(_) => return Err(std::io::Error::other("panicked")),
+    }
+
+    let mut res = serde_json::json!({
+        "ok": true,
+        "output": String::from_utf8_lossy(&b),
+    });
+    if let Some(v) = &ctx.aud {
+        res["passed"] = v.is_passed().into();
+    }
+    Ok(res)
+}
+
+fn handle_op(f: &str, ctx: &mut dir::Context, opt: &Opt) -> std::io::Result<()> {
+    if opt.hashdeep && ctx.aud.is_none() {
+        hashdeep::print_header(&opt.hashdeep_algo, &[f.to_string()], opt.null)?;
+    }
+    dir::print_input(f, ctx, opt)?;
+    if let Some(v) = &ctx.aud {
+        v.print_audit(opt);
+    }
+    Ok(())
+}
+
+fn get_request_opt(req: &serde_json::Value) -> std::io::Result<Opt> {
+    let mut opt = Opt::default();
+    if let Some(v) = req.get(REQUEST_HASH_ALGO) {
+        match v.as_str() {
+            Some(v) => v.clone_into(&mut opt.hash_algo),
+            None => return Err(get_invalid_error(REQUEST_HASH_ALGO)),
+        }
+    }
+    if let Some(v) = req.get(REQUEST_SQUASH_DEPTH) {
+        match v.as_u64().and_then(|v| usize::try_from(v).ok()) {
+            Some(v) => opt.squash_depth = v,
+            None => return Err(get_invalid_error(REQUEST_SQUASH_DEPTH)),
+        }
+    }
+    for k in REQUEST_BOOL {
+        let Some(v) = req.get(k) else {
+            continue;
+        };
+        let Some(v) = v.as_bool() else {
+            return Err(get_invalid_error(k));
+        };
+        match k {
+            "hash_only" => opt.hash_only = v,
+            "ignore_dot" => opt.ignore_dot = v,
+            "ignore_dot_dir" => opt.ignore_dot_dir = v,
+            "ignore_dot_file" => opt.ignore_dot_file = v,
+            "ignore_symlink" => opt.ignore_symlink = v,
+            "follow_symlink" => opt.follow_symlink = v,
+            "abs" => opt.abs = v,
+            "swap" => opt.swap = v,
+            "sort" => opt.sort = v,
+            "hashdeep" => opt.hashdeep = v,
+            "hardlink" => opt.hardlink = v,
+            _ => panic!("{k}"),
+        }
+    }
+    Ok(opt)
+}
+
+fn get_request_str<'a>(req: &'a serde_json::Value, k: &str) -> std::io::Result<&'a str> {
+    match req.get(k).and_then(serde_json::Value::as_str) {
+        Some(v) => Ok(v),
+        None => Err(get_invalid_error(k)),
+    }
+}
+
+fn get_invalid_error(s: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid {s}"))
+}
+
+// send request and return response
+pub(crate) fn request(f: &str, req: &serde_json::Value) -> std::io::Result<serde_json::Value> {
+    let mut stream = std::os::unix::net::UnixStream::connect(f)?;
+    writeln!(stream, "{req}")?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut line = String::new();
+    std::io::BufReader::new(stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_request_opt() {
+        let req = serde_json::json!({"hash_algo": "md5", "sort": true, "squash_depth": 2});
+        let opt = super::get_request_opt(&req).unwrap();
+        assert_eq!(opt.hash_algo, "md5");
+        assert!(opt.sort);
+        assert!(!opt.abs);
+        assert_eq!(opt.squash_depth, 2);
+
+        let invalid_list = [
+            serde_json::json!({"hash_algo": 1}),
+            serde_json::json!({"sort": "yes"}),
+            serde_json::json!({"squash_depth": -1}),
+        ];
+        for x in &invalid_list {
+            assert!(super::get_request_opt(x).is_err(), "{x}");
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        let d = crate::util::TempDir::new("serve");
+        let f = d.join("sock");
+        let f = f.to_str().unwrap().to_string();
+        let listener = super::bind(&f).unwrap();
+
+        // echo server which checks request is a single line
+        let t = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut w = stream.try_clone().unwrap();
+            for line in std::io::BufRead::lines(std::io::BufReader::new(stream)) {
+                std::io::Write::write_all(&mut w, format!("{}\n", line.unwrap()).as_bytes())
+                    .unwrap();
+            }
+        });
+        let req = serde_json::json!({"op": "hash", "path": "/a\nb"});
+        assert_eq!(super::request(&f, &req).unwrap(), req);
+        t.join().unwrap();
+
+        // stale socket replaced
+        assert!(super::bind(&f).is_ok());
+        let m = std::fs::metadata(&f).unwrap();
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&m.permissions()) & 0o777,
+            0o600
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use base64::Engine;
+use ed25519_dalek::Signer;
+use sha2::Digest;
+
+// signify compatible key and signature files, so keys can be generated
+// with "signify -G -n" and signatures checked with "signify -V"
+pub(crate) const SIGNATURE_SUFFIX: &str = ".sig";
+
+const PKALG: &[u8] = b"Ed";
+const KDFALG: &[u8] = b"BK";
+const COMMENT_PREFIX: &str = "untrusted comment: ";
+
+// pkalg, kdfalg, kdfrounds, salt, checksum, keynum, seckey
+const SECRET_KEY_SIZE: usize = 2 + 2 + 4 + 16 + 8 + 8 + 64;
+// pkalg, keynum, pubkey
+const PUBLIC_KEY_SIZE: usize = 2 + 8 + 32;
+// pkalg, keynum, sig
+const SIGNATURE_SIZE: usize = 2 + 8 + 64;
+
+#[derive(Debug)]
+pub(crate) struct SecretKey {
+    keynum: [u8; 8],
+    key: ed25519_dalek::SigningKey,
+}
+
+#[derive(Debug)]
+pub(crate) struct PublicKey {
+    keynum: [u8; 8],
+    key: ed25519_dalek::VerifyingKey,
+}
+
+impl SecretKey {
+    pub(crate) fn new(f: &str) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(f)?)
+    }
+
+    fn parse(s: &str) -> std::io::Result<Self> {
+        let b = decode_file(s, SECRET_KEY_SIZE)?;
+        if &b[0..2] != PKALG || &b[2..4] != KDFALG {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+        // passphrase protected key unsupported
+        if b[4..8] != [0; 4] {
+            return Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+        }
+        let seckey = &b[40..104];
+        if sha2::Sha512::digest(seckey)[..8] != b[24..32] {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+        let Ok(seed) = seckey[..32].try_into() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        };
+        let key = ed25519_dalek::SigningKey::from_bytes(&seed);
+        if key.verifying_key().as_bytes() != &seckey[32..] {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+        Ok(Self {
+            keynum: get_keynum(&b[32..40])?,
+            key,
+        })
+    }
+
+    fn get_signature_string(&self, m: &[u8]) -> String {
+        let mut b = PKALG.to_vec();
+        b.extend(self.keynum);
+        b.extend(self.key.sign(m).to_bytes());
+        format!(
+            "{COMMENT_PREFIX}signature from dirhash secret key\n{}\n",
+            base64::engine::general_purpose::STANDARD.encode(b)
+        )
+    }
+}
+
+impl PublicKey {
+    pub(crate) fn new(f: &str) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(f)?)
+    }
+
+    fn parse(s: &str) -> std::io::Result<Self> {
+        let b = decode_file(s, PUBLIC_KEY_SIZE)?;
+        if &b[0..2] != PKALG {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+        let Ok(key) = b[10..].try_into() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        };
+        let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(&key) else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        };
+        Ok(Self {
+            keynum: get_keynum(&b[2..10])?,
+            key,
+        })
+    }
+
+    fn verify_signature_string(&self, m: &[u8], s: &str) -> std::io::Result<bool> {
+        let b = decode_file(s, SIGNATURE_SIZE)?;
+        if &b[0..2] != PKALG || b[2..10] != self.keynum {
+            return Ok(false);
+        }
+        let Ok(sig) = b[10..].try_into() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        };
+        let sig = ed25519_dalek::Signature::from_bytes(&sig);
+        Ok(self.key.verify_strict(m, &sig).is_ok())
+    }
+}
+
+// write detached signature of f to f.sig, atomically as output file
+pub(crate) fn sign_file(f: &str, key: &SecretKey) -> std::io::Result<()> {
+    let s = key.get_signature_string(&std::fs::read(f)?);
+    crate::output::write_file(&format!("{f}{SIGNATURE_SUFFIX}"), &s)
+}
+
+// verify detached signature of f in f.sig
+pub(crate) fn verify_file(f: &str, key: &PublicKey) -> std::io::Result<bool> {
+    let s = std::fs::read_to_string(format!("{f}{SIGNATURE_SUFFIX}"))?;
+    key.verify_signature_string(&std::fs::read(f)?, &s)
+}
+
+// untrusted comment line followed by base64 line
+fn decode_file(s: &str, size: usize) -> std::io::Result<Vec<u8>> {
+    let mut lines = s.lines();
+    if !lines.next().is_some_and(|x| x.starts_with(COMMENT_PREFIX)) {
+        return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+    }
+    let Some(x) = lines.next() else {
+        return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+    };
+    match base64::engine::general_purpose::STANDARD.decode(x.trim()) {
+        Ok(v) if v.len() == size => Ok(v),
+        _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidData)),
+    }
+}
+
+fn get_keynum(b: &[u8]) -> std::io::Result<[u8; 8]> {
+    b.try_into()
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use sha2::Digest;
+
+    // unencrypted key pair in signify format from fixed seed
+    fn get_key_pair(seed: u8) -> (String, String) {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+        let keynum = [seed; 8];
+        let mut seckey = key.to_bytes().to_vec();
+        seckey.extend(key.verifying_key().as_bytes());
+
+        let mut b = b"EdBK".to_vec();
+        b.extend([0; 4 + 16]);
+        b.extend(&sha2::Sha512::digest(&seckey)[..8]);
+        b.extend(keynum);
+        b.extend(&seckey);
+        let sk = format!(
+            "untrusted comment: signify secret key\n{}\n",
+            base64::engine::general_purpose::STANDARD.encode(b)
+        );
+
+        let mut b = b"Ed".to_vec();
+        b.extend(keynum);
+        b.extend(key.verifying_key().as_bytes());
+        let pk = format!(
+            "untrusted comment: signify public key\n{}\n",
+            base64::engine::general_purpose::STANDARD.encode(b)
+        );
+        (sk, pk)
+    }
+
+    #[test]
+    fn test_signature() {
+        let (sk, pk) = get_key_pair(1);
+        let sk = super::SecretKey::parse(&sk).unwrap();
+        let pk = super::PublicKey::parse(&pk).unwrap();
+        let s = sk.get_signature_string(b"abc");
+        assert!(s.starts_with(super::COMMENT_PREFIX));
+        assert!(pk.verify_signature_string(b"abc", &s).unwrap());
+        assert!(!pk.verify_signature_string(b"abd", &s).unwrap());
+
+        // other key
+        let (_, pk) = get_key_pair(2);
+        let pk = super::PublicKey::parse(&pk).unwrap();
+        assert!(!pk.verify_signature_string(b"abc", &s).unwrap());
+    }
+
+    #[test]
+    fn test_sign_file() {
+        let d = crate::util::TempDir::new("sign");
+        let f = d.join("x");
+        let f = f.to_str().unwrap();
+        std::fs::write(f, "abc").unwrap();
+        let (sk, pk) = get_key_pair(1);
+        let sk = super::SecretKey::parse(&sk).unwrap();
+        let pk = super::PublicKey::parse(&pk).unwrap();
+        super::sign_file(f, &sk).unwrap();
+        assert!(super::verify_file(f, &pk).unwrap());
+        // no temp file left
+        assert_eq!(std::fs::read_dir(&d).unwrap().count(), 2);
+
+        std::fs::write(f, "abd").unwrap();
+        assert!(!super::verify_file(f, &pk).unwrap());
+    }
+
+    #[test]
+    fn test_parse() {
+        let (sk, pk) = get_key_pair(1);
+        assert!(super::SecretKey::parse(&pk).is_err());
+        assert!(super::PublicKey::parse(&sk).is_err());
+        let invalid_list = ["", "untrusted comment: x\n", "x\nRWQ=\n"];
+        for s in &invalid_list {
+            assert!(super::SecretKey::parse(s).is_err(), "{s}");
+            assert!(super::PublicKey::parse(s).is_err(), "{s}");
+        }
+
+        // passphrase protected
+        let mut b = base64::engine::general_purpose::STANDARD
+            .decode(sk.lines().nth(1).unwrap())
+            .unwrap();
+        b[7] = 42;
+        let s = format!(
+            "untrusted comment: x\n{}\n",
+            base64::engine::general_purpose::STANDARD.encode(b)
+        );
+        assert_eq!(
+            super::SecretKey::parse(&s).unwrap_err().kind(),
+            std::io::ErrorKind::Unsupported
+        );
+    }
+}
@@ -23,8 +23,9 @@ impl Squash {
     }
 
     pub(crate) fn update_buffer(&mut self, b: &[u8]) -> std::io::Result<()> {
-        let (b, ..) = hash::get_byte_hash(b, hash::MD5)?;
-        self.buffer.push(b);
+        let mut h = hash::new_unkeyed_hash(hash::MD5)?;
+        h.update(b);
+        self.buffer.push(h.finalize());
         Ok(())
     }
 
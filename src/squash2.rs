@@ -25,8 +25,9 @@ impl Squash {
     pub(crate) fn update_buffer(&mut self, b: &[u8]) -> std::io::Result<()> {
         // result depends on append order
         self.buffer.extend(b);
-        let (b, ..) = hash::get_byte_hash(&self.buffer, hash::SHA1)?;
-        self.buffer = b;
+        let mut h = hash::new_unkeyed_hash(hash::SHA1)?;
+        h.update(&self.buffer);
+        self.buffer = h.finalize();
         Ok(())
     }
 
@@ -2,20 +2,35 @@ use crate::dir;
 use crate::util;
 use crate::Opt;
 
+// number of slowest and largest files to keep
+const TOP_NUM: usize = 5;
+
 #[derive(Debug, Default)]
 pub(crate) struct Stat {
     stat_directory: Vec<String>, // hashed
     stat_regular: Vec<String>,   // hashed
     stat_device: Vec<String>,    // hashed
     stat_symlink: Vec<String>,   // hashed
+    stat_special: Vec<String>,   // hashed, FIFO or socket
     stat_unsupported: Vec<String>,
     stat_invalid: Vec<String>,
     stat_ignored: Vec<String>,
+    stat_filtered: Vec<String>,
+    stat_hardlink: Vec<String>, // subset of regular
+    stat_reused: Vec<String>,   // subset of regular or device
 
     written_directory: usize, // hashed
     written_regular: usize,   // hashed
     written_device: usize,    // hashed
     written_symlink: usize,   // hashed
+    written_hole: usize,      // subset of regular, hashed without read
+
+    time_start: Option<std::time::Instant>,
+    time_regular: std::time::Duration, // hashed
+    time_device: std::time::Duration,  // hashed
+    slowest: Vec<(std::time::Duration, String)>,
+    largest: Vec<(u64, String)>,
+    device_size: Vec<(u64, String)>, // block devices hashed
 }
 
 impl Stat {
@@ -32,14 +47,26 @@ impl Stat {
         self.stat_regular.clear();
         self.stat_device.clear();
         self.stat_symlink.clear();
+        self.stat_special.clear();
         self.stat_unsupported.clear();
         self.stat_invalid.clear();
         self.stat_ignored.clear();
+        self.stat_filtered.clear();
+        self.stat_hardlink.clear();
+        self.stat_reused.clear();
 
         self.written_directory = 0;
         self.written_regular = 0;
         self.written_device = 0;
         self.written_symlink = 0;
+        self.written_hole = 0;
+
+        self.time_start = Some(std::time::Instant::now());
+        self.time_regular = std::time::Duration::ZERO;
+        self.time_device = std::time::Duration::ZERO;
+        self.slowest.clear();
+        self.largest.clear();
+        self.device_size.clear();
     }
 
     // num stat
@@ -48,6 +75,7 @@ impl Stat {
             + self.num_stat_regular()
             + self.num_stat_device()
             + self.num_stat_symlink()
+            + self.num_stat_special()
     }
 
     pub(crate) fn num_stat_directory(&self) -> usize {
@@ -66,6 +94,10 @@ impl Stat {
         self.stat_symlink.len()
     }
 
+    pub(crate) fn num_stat_special(&self) -> usize {
+        self.stat_special.len()
+    }
+
     #[allow(dead_code)]
     pub(crate) fn num_stat_unsupported(&self) -> usize {
         self.stat_unsupported.len()
@@ -81,6 +113,19 @@ impl Stat {
         self.stat_ignored.len()
     }
 
+    #[allow(dead_code)]
+    pub(crate) fn num_stat_filtered(&self) -> usize {
+        self.stat_filtered.len()
+    }
+
+    pub(crate) fn num_stat_hardlink(&self) -> usize {
+        self.stat_hardlink.len()
+    }
+
+    pub(crate) fn num_stat_reused(&self) -> usize {
+        self.stat_reused.len()
+    }
+
     // append stat
     pub(crate) fn append_stat_total(&self) {}
 
@@ -100,6 +145,10 @@ impl Stat {
         self.stat_symlink.push(f.to_string());
     }
 
+    pub(crate) fn append_stat_special(&mut self, f: &str) {
+        self.stat_special.push(f.to_string());
+    }
+
     pub(crate) fn append_stat_unsupported(&mut self, f: &str) {
         self.stat_unsupported.push(f.to_string());
     }
@@ -112,6 +161,43 @@ impl Stat {
         self.stat_ignored.push(f.to_string());
     }
 
+    pub(crate) fn append_stat_filtered(&mut self, f: &str) {
+        self.stat_filtered.push(f.to_string());
+    }
+
+    pub(crate) fn append_stat_hardlink(&mut self, f: &str) {
+        self.stat_hardlink.push(f.to_string());
+    }
+
+    pub(crate) fn append_stat_reused(&mut self, f: &str) {
+        self.stat_reused.push(f.to_string());
+    }
+
+    // f and files under f if directory, e.g. changed after walk,
+    // written bytes and time kept as running total
+    pub(crate) fn remove_stat(&mut self, f: &str) {
+        let prefix = format!("{f}/");
+        let keep = |x: &String| x != f && !x.starts_with(&prefix);
+        for l in [
+            &mut self.stat_directory,
+            &mut self.stat_regular,
+            &mut self.stat_device,
+            &mut self.stat_symlink,
+            &mut self.stat_special,
+            &mut self.stat_unsupported,
+            &mut self.stat_invalid,
+            &mut self.stat_ignored,
+            &mut self.stat_filtered,
+            &mut self.stat_hardlink,
+            &mut self.stat_reused,
+        ] {
+            l.retain(keep);
+        }
+        self.slowest.retain(|x| keep(&x.1));
+        self.largest.retain(|x| keep(&x.1));
+        self.device_size.retain(|x| keep(&x.1));
+    }
+
     // print stat
     #[allow(dead_code)]
     pub(crate) fn print_stat_directory(&self, inp: &str, opt: &Opt) -> std::io::Result<()> {
@@ -160,6 +246,10 @@ impl Stat {
         self.print_stat(&self.stat_ignored, "ignored file", inp, opt)
     }
 
+    pub(crate) fn print_stat_filtered(&self, inp: &str, opt: &Opt) -> std::io::Result<()> {
+        self.print_stat(&self.stat_filtered, "filtered file", inp, opt)
+    }
+
     fn print_stat(&self, l: &[String], msg: &str, inp: &str, opt: &Opt) -> std::io::Result<()> {
         if l.is_empty() {
             return Ok(());
@@ -168,17 +258,26 @@ impl Stat {
 
         for v in l {
             let f = dir::get_real_path(v, inp, opt);
+            // archive member does not exist on filesystem
+            if opt.archive && util::get_raw_file_type(v).is_err() {
+                println!("{f} (archive member)");
+                continue;
+            }
             let t1 = util::get_raw_file_type(v)?;
             let t2 = match util::get_file_type(v) {
                 Ok(v) => v,
                 Err(_) => util::FileType::Invalid, // e.g. broken symlink
             };
             assert!(!t2.is_symlink()); // symlink chains resolved
+
+            // filter may select any type
             if t1.is_symlink() {
-                assert!(opt.ignore_symlink || t2.is_dir() || t2.is_invalid());
+                assert!(
+                    opt.ignore_symlink || t2.is_dir() || t2.is_invalid() || !opt.filter.is_empty()
+                );
                 println!("{} ({} -> {})", f, t1.as_str(), t2.as_str());
             } else {
-                assert!(!t2.is_dir());
+                assert!(!t2.is_dir() || !opt.filter.is_empty());
                 println!("{} ({})", f, t1.as_str());
             }
         }
@@ -209,6 +308,10 @@ impl Stat {
         self.written_symlink
     }
 
+    pub(crate) fn num_written_hole(&self) -> usize {
+        self.written_hole
+    }
+
     // append written
     pub(crate) fn append_written_total(&self, _written: u64) {}
 
@@ -227,6 +330,78 @@ impl Stat {
     pub(crate) fn append_written_symlink(&mut self, written: u64) {
         self.written_symlink += usize::try_from(written).unwrap();
     }
+
+    pub(crate) fn append_written_hole(&mut self, written: u64) {
+        self.written_hole += usize::try_from(written).unwrap();
+    }
+
+    // num time
+    pub(crate) fn num_time_wall(&self) -> std::time::Duration {
+        match self.time_start {
+            Some(v) => v.elapsed(),
+            None => std::time::Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn num_time_hash(&self) -> std::time::Duration {
+        self.num_time_regular() + self.num_time_device()
+    }
+
+    // not hashing regular files or devices, e.g. directory walk
+    pub(crate) fn num_time_walk(&self) -> std::time::Duration {
+        self.num_time_wall().saturating_sub(self.num_time_hash())
+    }
+
+    pub(crate) fn num_time_regular(&self) -> std::time::Duration {
+        self.time_regular
+    }
+
+    pub(crate) fn num_time_device(&self) -> std::time::Duration {
+        self.time_device
+    }
+
+    pub(crate) fn get_slowest(&self) -> &[(std::time::Duration, String)] {
+        &self.slowest
+    }
+
+    pub(crate) fn get_largest(&self) -> &[(u64, String)] {
+        &self.largest
+    }
+
+    pub(crate) fn get_device_size(&self) -> &[(u64, String)] {
+        &self.device_size
+    }
+
+    pub(crate) fn append_device_size(&mut self, f: &str, size: u64) {
+        self.device_size.push((size, f.to_string()));
+    }
+
+    // append time
+    pub(crate) fn append_time(
+        &mut self,
+        f: &str,
+        t: &util::FileType,
+        d: std::time::Duration,
+        written: u64,
+    ) {
+        match t {
+            util::FileType::Reg => self.time_regular += d,
+            util::FileType::Device => self.time_device += d,
+            _ => util::panic_file_type(f, "invalid", t),
+        }
+        append_top(&mut self.slowest, d, f);
+        append_top(&mut self.largest, written, f);
+    }
+}
+
+// keep TOP_NUM entries in descending order, earlier one first if equal
+fn append_top<T: Ord>(l: &mut Vec<(T, String)>, v: T, f: &str) {
+    if l.len() >= TOP_NUM && v <= l[l.len() - 1].0 {
+        return;
+    }
+    let i = l.partition_point(|x| x.0 >= v);
+    l.insert(i, (v, f.to_string()));
+    l.truncate(TOP_NUM);
 }
 
 #[cfg(test)]
@@ -270,6 +445,20 @@ mod tests {
         assert_eq!(stat.stat_regular[0], "d");
     }
 
+    #[test]
+    fn test_remove_stat() {
+        let mut stat = super::Stat::new();
+        for f in ["/a", "/a/b", "/a-b", "/ab", "/c"] {
+            stat.append_stat_regular(f);
+        }
+        stat.append_stat_directory("/a");
+        stat.append_device_size("/a/b", 1);
+        stat.remove_stat("/a");
+        assert_eq!(stat.stat_regular, ["/a-b", "/ab", "/c"]);
+        assert_eq!(stat.num_stat_directory(), 0);
+        assert!(stat.get_device_size().is_empty());
+    }
+
     #[test]
     fn test_num_written_regular() {
         let mut stat = super::Stat::new();
@@ -291,4 +480,83 @@ mod tests {
         stat.init_stat();
         assert_eq!(stat.num_written_regular(), 0);
     }
+
+    #[test]
+    fn test_append_stat_hardlink() {
+        let mut stat = super::Stat::new();
+        assert_eq!(stat.num_stat_hardlink(), 0);
+        assert_eq!(stat.num_stat_reused(), 0);
+
+        stat.append_stat_regular("a");
+        stat.append_stat_hardlink("a");
+        stat.append_stat_regular("b");
+        stat.append_stat_hardlink("b");
+        stat.append_stat_reused("b");
+        assert_eq!(stat.num_stat_regular(), 2);
+        assert_eq!(stat.num_stat_hardlink(), 2);
+        assert_eq!(stat.num_stat_reused(), 1);
+        assert_eq!(stat.num_stat_total(), 2);
+
+        stat.init_stat();
+        assert_eq!(stat.num_stat_hardlink(), 0);
+        assert_eq!(stat.num_stat_reused(), 0);
+    }
+
+    #[test]
+    fn test_append_written_hole() {
+        let mut stat = super::Stat::new();
+        assert_eq!(stat.num_written_hole(), 0);
+
+        stat.append_written_regular(10);
+        stat.append_written_hole(4);
+        assert_eq!(stat.num_written_hole(), 4);
+        assert_eq!(stat.num_written_total(), 10);
+
+        stat.init_stat();
+        assert_eq!(stat.num_written_hole(), 0);
+    }
+
+    #[test]
+    fn test_append_time() {
+        let mut stat = super::Stat::new();
+        assert_eq!(stat.num_time_hash(), std::time::Duration::ZERO);
+        assert!(stat.get_slowest().is_empty());
+        assert!(stat.get_largest().is_empty());
+
+        let ms = std::time::Duration::from_millis;
+        let file_list = [
+            ("a", &crate::util::FileType::Reg, 3, 10),
+            ("b", &crate::util::FileType::Reg, 1, 50),
+            ("c", &crate::util::FileType::Device, 5, 30),
+            ("d", &crate::util::FileType::Reg, 3, 10),
+            ("e", &crate::util::FileType::Reg, 2, 20),
+            ("f", &crate::util::FileType::Reg, 4, 40),
+            ("g", &crate::util::FileType::Reg, 1, 5),
+        ];
+        for x in &file_list {
+            stat.append_time(x.0, x.1, ms(x.2), x.3);
+        }
+        assert_eq!(stat.num_time_regular(), ms(14));
+        assert_eq!(stat.num_time_device(), ms(5));
+        assert_eq!(stat.num_time_hash(), ms(19));
+        assert!(stat.num_time_wall() >= stat.num_time_walk());
+
+        let l: Vec<(u64, &str)> = stat
+            .get_slowest()
+            .iter()
+            .map(|x| (u64::try_from(x.0.as_millis()).unwrap(), x.1.as_str()))
+            .collect();
+        assert_eq!(l, [(5, "c"), (4, "f"), (3, "a"), (3, "d"), (2, "e")]);
+        let l: Vec<(u64, &str)> = stat
+            .get_largest()
+            .iter()
+            .map(|x| (x.0, x.1.as_str()))
+            .collect();
+        assert_eq!(l, [(50, "b"), (40, "f"), (30, "c"), (20, "e"), (10, "a")]);
+
+        stat.init_stat();
+        assert_eq!(stat.num_time_hash(), std::time::Duration::ZERO);
+        assert!(stat.get_slowest().is_empty());
+        assert!(stat.get_largest().is_empty());
+    }
 }
@@ -0,0 +1,137 @@
+use crate::Squash;
+
+// squash of input, and of each subdirectory at given depth below input prefix
+#[derive(Debug, Default)]
+pub(crate) struct SubSquash {
+    root: Squash,
+    sub: std::collections::BTreeMap<String, Squash>,
+    cur: Option<String>,
+    depth: usize, // 0 if no subdirectory squash
+}
+
+impl SubSquash {
+    pub(crate) fn new(depth: usize) -> Self {
+        Self {
+            root: Squash::new(),
+            depth,
+            ..Default::default()
+        }
+    }
+
+    // subsequent updates also go to subdirectory squash of f if any
+    pub(crate) fn set_entry(&mut self, f: &str, is_dir: bool, inp: &str) {
+        self.cur = if self.depth > 0 {
+            get_subdir(f, is_dir, inp, self.depth)
+        } else {
+            None
+        };
+    }
+
+    pub(crate) fn update_buffer(&mut self, b: &[u8]) -> std::io::Result<()> {
+        self.root.update_buffer(b)?;
+        if let Some(k) = &self.cur {
+            self.sub.entry(k.clone()).or_default().update_buffer(b)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_buffer(&self) -> Vec<u8> {
+        self.root.get_buffer()
+    }
+
+    // subdirectory path and buffer in path order
+    pub(crate) fn get_sub_buffer(&self) -> Vec<(&str, Vec<u8>)> {
+        self.sub
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.get_buffer()))
+            .collect()
+    }
+}
+
+// ancestor of f at depth, or f itself if directory at depth
+fn get_subdir(f: &str, is_dir: bool, inp: &str, depth: usize) -> Option<String> {
+    let x = if inp == "/" {
+        f.strip_prefix('/')?
+    } else {
+        f.strip_prefix(inp)?.strip_prefix('/')?
+    };
+    let l: Vec<&str> = x.split('/').collect();
+    if l.len() > depth || (l.len() == depth && is_dir) {
+        let s = l[..depth].join("/");
+        Some(if inp == "/" {
+            format!("/{s}")
+        } else {
+            format!("{inp}/{s}")
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_subdir() {
+        let subdir_list = [
+            ("/a/b", false, "/a", 1, None),
+            ("/a/b", true, "/a", 1, Some("/a/b")),
+            ("/a/b/c", false, "/a", 1, Some("/a/b")),
+            ("/a/b/c/d", true, "/a", 1, Some("/a/b")),
+            ("/a/b/c", true, "/a", 2, Some("/a/b/c")),
+            ("/a/b/c", false, "/a", 2, None),
+            ("/a/b/c/d", false, "/a", 2, Some("/a/b/c")),
+            ("/a", true, "/a", 1, None),
+            ("/ab/c", false, "/a", 1, None),
+            ("/a/b", false, "/", 1, Some("/a")),
+            ("/a", true, "/", 1, Some("/a")),
+        ];
+        for x in &subdir_list {
+            assert_eq!(
+                super::get_subdir(x.0, x.1, x.2, x.3).as_deref(),
+                x.4,
+                "{x:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_buffer() {
+        let mut squ = super::SubSquash::new(1);
+        let entry_list = [
+            ("/a", true),
+            ("/a/x", false),
+            ("/a/b", true),
+            ("/a/b/y", false),
+            ("/a/c", true),
+            ("/a/c/d", true),
+            ("/a/c/d/z", false),
+        ];
+        for x in &entry_list {
+            squ.set_entry(x.0, x.1, "/a");
+            squ.update_buffer(x.0.as_bytes()).unwrap();
+        }
+
+        let mut b = crate::Squash::new();
+        let mut c = crate::Squash::new();
+        for x in &entry_list[2..4] {
+            b.update_buffer(x.0.as_bytes()).unwrap();
+        }
+        for x in &entry_list[4..] {
+            c.update_buffer(x.0.as_bytes()).unwrap();
+        }
+        let l = squ.get_sub_buffer();
+        assert_eq!(l, [("/a/b", b.get_buffer()), ("/a/c", c.get_buffer())]);
+
+        // input squash unaffected
+        let mut a = crate::Squash::new();
+        for x in &entry_list {
+            a.update_buffer(x.0.as_bytes()).unwrap();
+        }
+        assert_eq!(squ.get_buffer(), a.get_buffer());
+
+        let mut squ = super::SubSquash::new(0);
+        squ.set_entry("/a/b/y", false, "/a");
+        squ.update_buffer(&[]).unwrap();
+        assert!(squ.get_sub_buffer().is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::backend;
+use crate::hash;
+
+// files in flight per submission
+const QUEUE_DEPTH: usize = 64;
+
+// read and hash files of size below buffer size, each in a single read
+// None if not read in full, caller falls back to synchronous read
+pub(crate) fn read_files(
+    fl: &[(String, u64)],
+    hash_algo: &[String],
+    bk: &backend::Backend,
+) -> std::io::Result<Vec<Option<hash::SparseSum>>> {
+    // e.g. kernel without io_uring or disabled by seccomp
+    let Ok(mut ring) = io_uring::IoUring::new(u32::try_from(QUEUE_DEPTH * 2).unwrap()) else {
+        return Ok(vec![None; fl.len()]);
+    };
+    let mut ret = vec![];
+    for l in fl.chunks(QUEUE_DEPTH) {
+        ret.extend(read_files_impl(&mut ring, l, hash_algo, bk)?);
+    }
+    Ok(ret)
+}
+
+fn read_files_impl(
+    ring: &mut io_uring::IoUring,
+    fl: &[(String, u64)],
+    hash_algo: &[String],
+    bk: &backend::Backend,
+) -> std::io::Result<Vec<Option<hash::SparseSum>>> {
+    let mut ret = vec![None; fl.len()];
+
+    // batch openat
+    let mut pathl = vec![];
+    for (f, _) in fl {
+        match std::ffi::CString::new(f.as_str()) {
+            Ok(v) => pathl.push(v),
+            Err(_) => return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
+        }
+    }
+    let mut entl = vec![];
+    for (i, x) in pathl.iter().enumerate() {
+        entl.push(
+            io_uring::opcode::OpenAt::new(io_uring::types::Fd(libc::AT_FDCWD), x.as_ptr())
+                .flags(libc::O_RDONLY | libc::O_CLOEXEC)
+                .build()
+                .user_data(i.try_into().unwrap()),
+        );
+    }
+    let mut fdl = vec![None; fl.len()];
+    for (i, res) in submit_and_wait(ring, &entl)? {
+        if res >= 0 {
+            fdl[i] = Some(res);
+        }
+    }
+
+    // batch read, hash buffers in order of completion
+    let mut bufl: Vec<backend::Buffer> = fl.iter().map(|_| bk.alloc_buffer()).collect();
+    let mut entl = vec![];
+    for (i, fd) in fdl.iter().enumerate() {
+        if let Some(fd) = fd {
+            let buf = bufl[i].as_mut_slice();
+            entl.push(
+                io_uring::opcode::Read::new(
+                    io_uring::types::Fd(*fd),
+                    buf.as_mut_ptr(),
+                    u32::try_from(buf.len()).unwrap_or(u32::MAX),
+                )
+                .build()
+                .user_data(i.try_into().unwrap()),
+            );
+        }
+    }
+    for (i, res) in submit_and_wait(ring, &entl)? {
+        let Ok(n) = usize::try_from(res) else {
+            continue;
+        };
+        // short read may not be EOF, e.g. network filesystem,
+        // nor size at stat if file changed since
+        if n < bufl[i].len() && u64::try_from(n).unwrap() == fl[i].1 {
+            let b = &bufl[i].as_mut_slice()[..n];
+            let (sum, written) = hash::get_hash_multi(&mut &*b, hash_algo)?;
+            ret[i] = Some((sum, written, 0));
+        }
+    }
+
+    // batch close
+    let mut entl = vec![];
+    for (i, fd) in fdl.iter().enumerate() {
+        if let Some(fd) = fd {
+            entl.push(
+                io_uring::opcode::Close::new(io_uring::types::Fd(*fd))
+                    .build()
+                    .user_data(i.try_into().unwrap()),
+            );
+        }
+    }
+    submit_and_wait(ring, &entl)?;
+    Ok(ret)
+}
+
+// index and result of each entry in order of completion
+fn submit_and_wait(
+    ring: &mut io_uring::IoUring,
+    entl: &[io_uring::squeue::Entry],
+) -> std::io::Result<Vec<(usize, i32)>> {
+    assert!(entl.len() <= QUEUE_DEPTH);
+    // buffers and paths outlive completions
+    unsafe {
+        if ring.submission().push_multiple(entl).is_err() {
+            return Err(std::io::Error::from(std::io::ErrorKind::OutOfMemory));
+        }
+    }
+    let mut ret = vec![];
+    while ret.len() < entl.len() {
+        ring.submit_and_wait(entl.len() - ret.len())?;
+        for cqe in ring.completion() {
+            ret.push((usize::try_from(cqe.user_data()).unwrap(), cqe.result()));
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_read_files() {
+        let d = crate::util::TempDir::new("uring");
+        let hash_algo = [
+            crate::hash::MD5.to_string(),
+            crate::hash::SHA256.to_string(),
+        ];
+        let bk = crate::backend::Backend::new(crate::backend::IO_URING, 4096, false).unwrap();
+
+        // more files than queue depth, last two not read in full
+        let mut file_list = vec![];
+        for i in 0..super::QUEUE_DEPTH * 2 + 1 {
+            file_list.push((format!("{i}"), "x".repeat(i * 10)));
+        }
+        file_list.push(("full".to_string(), "x".repeat(4096)));
+        for x in &file_list {
+            std::fs::write(d.join(&x.0), &x.1).unwrap();
+        }
+        let mut fl: Vec<(String, u64)> = file_list
+            .iter()
+            .map(|x| {
+                let f = d.join(&x.0).to_str().unwrap().to_string();
+                (f, x.1.len().try_into().unwrap())
+            })
+            .collect();
+        fl.push((d.join("noent").to_str().unwrap().to_string(), 0));
+        // size at stat differs from size read
+        fl[1].1 += 1;
+
+        let sl = match super::read_files(&fl, &hash_algo, &bk) {
+            Ok(v) => v,
+            Err(e) => panic!("{e}"),
+        };
+        assert_eq!(sl.len(), fl.len());
+        assert!(sl[fl.len() - 1].is_none());
+
+        // nothing read if io_uring unavailable, e.g. seccomp in container
+        if sl.iter().all(Option::is_none) {
+            return;
+        }
+        for (i, x) in file_list.iter().enumerate() {
+            if x.1.len() >= 4096 || i == 1 {
+                assert!(sl[i].is_none(), "{}", x.0);
+                continue;
+            }
+            let mut r = std::io::BufReader::new(x.1.as_bytes());
+            let (b, written) = crate::hash::get_hash_multi(&mut r, &hash_algo).unwrap();
+            assert_eq!(sl[i], Some((b, written, 0)), "{}", x.0);
+        }
+    }
+}
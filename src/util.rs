@@ -1,4 +1,5 @@
 use path_clean::PathClean;
+use std::io::Write;
 use std::os::unix::fs::FileTypeExt;
 
 #[derive(Debug)]
@@ -179,6 +180,58 @@ pub(crate) fn get_xsum_format_string(f: &str, h: &str, swap: bool) -> String {
     }
 }
 
+thread_local! {
+    // records go here instead of stdout if set, e.g. output file,
+    // while other prints such as stat stay on stdout
+    static RECORD_WRITER: std::cell::RefCell<Option<Box<dyn std::io::Write>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+// previous writer returned
+pub(crate) fn set_record_writer(
+    w: Option<Box<dyn std::io::Write>>,
+) -> Option<Box<dyn std::io::Write>> {
+    RECORD_WRITER.with_borrow_mut(|v| std::mem::replace(v, w))
+}
+
+// NUL terminated if specified, compatible with shaXsum -z
+pub(crate) fn print_record(s: &str, null: bool) {
+    let term = if null { '\0' } else { '\n' };
+    RECORD_WRITER.with_borrow_mut(|w| match w {
+        Some(w) => {
+            if let Err(e) = write!(w, "{s}{term}") {
+                panic!("{e}");
+            }
+        }
+        None => {
+            crate::progress::clear_line();
+            print!("{s}{term}");
+        }
+    });
+}
+
+// records printed by f, e.g. to compare walks
+#[cfg(test)]
+pub(crate) fn capture_record(f: impl FnOnce()) -> String {
+    #[derive(Clone, Default)]
+    struct Buffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+    impl std::io::Write for Buffer {
+        fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(b);
+            Ok(b.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let b = Buffer::default();
+    let old = set_record_writer(Some(Box::new(b.clone())));
+    f();
+    set_record_writer(old);
+    let v = b.0.borrow().clone();
+    String::from_utf8(v).unwrap()
+}
+
 pub(crate) fn get_num_format_string(n: usize, msg: &str) -> String {
     if msg.is_empty() {
         return "???".to_string();
@@ -208,8 +261,91 @@ pub(crate) fn panic_file_type(f: &str, how: &str, t: &FileType) {
     }
 }
 
+// path list from file or stdin if "-"
+pub(crate) fn read_path_list(f: &str, null: bool) -> std::io::Result<Vec<String>> {
+    let mut b = vec![];
+    if f == "-" {
+        std::io::Read::read_to_end(&mut std::io::stdin().lock(), &mut b)?;
+    } else {
+        b = std::fs::read(f)?;
+    }
+    let s =
+        String::from_utf8(b).map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+    split_path_list(&s, null)
+}
+
+// NUL or newline separated, empty entries skipped,
+// NUL in newline separated list is likely missing --null_input
+fn split_path_list(s: &str, null: bool) -> std::io::Result<Vec<String>> {
+    if !null && s.contains('\0') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "NUL in newline separated paths, use --null_input",
+        ));
+    }
+    let sep = if null { '\0' } else { '\n' };
+    Ok(s.split(sep)
+        .filter(|x| !x.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+// test directory removed on drop, hence also if assertion fails
+#[cfg(test)]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new(name: &str) -> Self {
+        let d = std::env::temp_dir().join(format!("dirhash-rs-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&d); // left by killed run
+        std::fs::create_dir_all(&d).unwrap();
+        Self(d)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempDir {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_split_path_list() {
+        let path_list = [
+            ("", false, vec![]),
+            ("a\nb c\n", false, vec!["a", "b c"]),
+            ("a\n\nb", false, vec!["a", "b"]),
+            ("a\0b\nc\0", true, vec!["a", "b\nc"]),
+        ];
+        for x in &path_list {
+            assert_eq!(super::split_path_list(x.0, x.1).unwrap(), x.2, "{:?}", x.0);
+        }
+        assert_eq!(
+            super::split_path_list("a\0b\n", false).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
     #[test]
     fn test_canonicalize_path() {
         let path_list = [
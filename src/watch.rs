@@ -0,0 +1,243 @@
+use crate::dir;
+use crate::stat;
+use crate::util;
+use crate::Opt;
+use std::io::Write;
+
+// wait for related events to arrive before handling them,
+// e.g. create followed by close_write, or editor saving via rename
+const SETTLE_TIME: std::time::Duration = std::time::Duration::from_millis(100);
+
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+type WatchMap = std::collections::HashMap<inotify::WatchDescriptor, String>;
+
+// input as specified, abs input path and input prefix,
+// with stat kept running across changes
+#[derive(Debug)]
+struct Watch {
+    arg: String,
+    f: String,
+    inp: String,
+    sta: stat::Stat,
+}
+
+// never returns unless error, inputs are expected to be walked already
+// with stat of each input unless single squash hash
+pub(crate) fn watch_input(
+    l: &[String],
+    sta_list: Vec<stat::Stat>,
+    ctx: &mut dir::Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    let mut ino = inotify::Inotify::init()?;
+    let mut wd = WatchMap::new();
+    let mut wl = vec![];
+    let mut sta_list = sta_list.into_iter();
+    for x in l {
+        let sta = sta_list.next().unwrap_or_default();
+        let Some((f, arc, inp)) = dir::get_input(x, opt)? else {
+            continue;
+        };
+        if arc.is_some() {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidInput));
+        }
+        // parent directory unless input is directory
+        if util::get_raw_file_type(&f)?.is_dir() {
+            add_watch(&mut ino, &f, &mut wd);
+        } else {
+            add_watch(&mut ino, &inp, &mut wd);
+        }
+        wl.push(Watch {
+            arg: x.clone(),
+            f,
+            inp,
+            sta,
+        });
+    }
+
+    let mut buf = [0; EVENT_BUFFER_SIZE];
+    loop {
+        let mut changed = std::collections::BTreeSet::new();
+        let mut overflow = read_events(&mut ino, &mut buf, &mut wd, &mut changed, true)?;
+        std::thread::sleep(SETTLE_TIME);
+        overflow |= read_events(&mut ino, &mut buf, &mut wd, &mut changed, false)?;
+
+        // walk every input again if events were lost
+        if overflow {
+            changed.extend(wl.iter().map(|w| w.f.clone()));
+        }
+
+        // new directories may have been populated before being watched
+        for f in &changed {
+            if util::get_raw_file_type(f).is_ok_and(|t| t.is_dir()) {
+                add_watch(&mut ino, f, &mut wd);
+            }
+        }
+        handle_change(&changed, l, &mut wl, ctx, opt)?;
+        std::io::stdout().flush()?;
+    }
+}
+
+fn handle_change(
+    changed: &std::collections::BTreeSet<String>,
+    l: &[String],
+    wl: &mut [Watch],
+    ctx: &mut dir::Context,
+    opt: &Opt,
+) -> std::io::Result<()> {
+    // single squash hash of all inputs
+    if opt.squash && !opt.files_from.is_empty() {
+        if wl.iter().any(|w| changed.iter().any(|x| is_under(x, &w.f))) {
+            dir::print_input_list(l, ctx, opt)?;
+        }
+        return Ok(());
+    }
+
+    for w in wl {
+        let fl = get_change_list(changed, &w.f);
+        if fl.is_empty() {
+            continue;
+        }
+        // squash hash of whole input, unchanged files are not read again
+        if opt.squash {
+            dir::print_input(&w.arg, ctx, opt)?;
+            continue;
+        }
+        for f in &fl {
+            dir::print_change(f, &w.inp, &mut w.sta, ctx, opt)?;
+        }
+        if opt.verbose {
+            dir::print_verbose_stat(&w.inp, &mut w.sta, opt)?;
+        }
+    }
+    Ok(())
+}
+
+// changed paths under input, excluding those under another changed path
+fn get_change_list(changed: &std::collections::BTreeSet<String>, f: &str) -> Vec<String> {
+    let mut l: Vec<String> = vec![];
+    for x in changed {
+        if is_under(x, f) && !l.iter().any(|y| is_under(x, y)) {
+            l.push(x.clone());
+        }
+    }
+    l
+}
+
+fn is_under(x: &str, f: &str) -> bool {
+    x == f || x.strip_prefix(f).is_some_and(|v| v.starts_with('/'))
+}
+
+// directories under f, errors ignored as they may be gone already
+fn add_watch(ino: &mut inotify::Inotify, f: &str, wd: &mut WatchMap) {
+    let mask = inotify::WatchMask::CLOSE_WRITE
+        | inotify::WatchMask::CREATE
+        | inotify::WatchMask::DELETE
+        | inotify::WatchMask::MOVED_FROM
+        | inotify::WatchMask::MOVED_TO;
+    for entry in walkdir::WalkDir::new(f)
+        .into_iter()
+        .filter_map(std::result::Result::ok)
+    {
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let Some(x) = entry.path().to_str() else {
+            continue;
+        };
+        if let Ok(v) = ino.watches().add(x, mask) {
+            wd.insert(v, x.to_string());
+        }
+    }
+}
+
+// true if event queue overflowed
+fn read_events(
+    ino: &mut inotify::Inotify,
+    buf: &mut [u8],
+    wd: &mut WatchMap,
+    changed: &mut std::collections::BTreeSet<String>,
+    blocking: bool,
+) -> std::io::Result<bool> {
+    let mut overflow = false;
+    loop {
+        let events = if blocking {
+            ino.read_events_blocking(buf)?
+        } else {
+            match ino.read_events(buf) {
+                Ok(v) => v,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        };
+        for e in events {
+            if e.mask.contains(inotify::EventMask::Q_OVERFLOW) {
+                overflow = true;
+                continue;
+            }
+            // watched directory removed
+            if e.mask.contains(inotify::EventMask::IGNORED) {
+                wd.remove(&e.wd);
+                continue;
+            }
+            let (Some(d), Some(name)) = (wd.get(&e.wd), e.name) else {
+                continue;
+            };
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            changed.insert(if d == "/" {
+                format!("/{name}")
+            } else {
+                format!("{d}/{name}")
+            });
+        }
+        if blocking {
+            break;
+        }
+    }
+    Ok(overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_get_change_list() {
+        let changed: std::collections::BTreeSet<String> =
+            ["/a/b", "/a/b/c", "/a/b-c", "/a/bc", "/x/y", "/a"]
+                .iter()
+                .map(|x| (*x).to_string())
+                .collect();
+        assert_eq!(super::get_change_list(&changed, "/a/b"), ["/a/b"]);
+        assert_eq!(super::get_change_list(&changed, "/a"), ["/a"]);
+        assert_eq!(super::get_change_list(&changed, "/x"), ["/x/y"]);
+        assert!(super::get_change_list(&changed, "/z").is_empty());
+        assert!(super::get_change_list(&changed, "/a/b/c/d").is_empty());
+    }
+
+    #[test]
+    fn test_print_change_hardlink() {
+        let d = crate::util::TempDir::new("watch");
+        std::fs::write(d.join("a"), "abc").unwrap();
+        std::fs::hard_link(d.join("a"), d.join("b")).unwrap();
+        let inp = d.to_str().unwrap();
+        let opt = crate::Opt::default();
+        let mut ctx = crate::dir::Context::new();
+        ctx.cac = Some(crate::cache::Cache::new());
+        let mut sta = crate::dir::print_input(inp, &mut ctx, &opt).unwrap();
+        assert_eq!(sta.num_stat_regular(), 2);
+        assert_eq!(sta.num_stat_reused(), 1);
+        let written = sta.num_written_regular();
+
+        // modified via one link, both hashed again as one inode
+        std::fs::write(d.join("a"), "abcd").unwrap();
+        for x in ["a", "b"] {
+            let f = format!("{inp}/{x}");
+            crate::dir::print_change(&f, inp, &mut sta, &mut ctx, &opt).unwrap();
+        }
+        assert_eq!(sta.num_stat_regular(), 2);
+        assert_eq!(sta.num_stat_reused(), 1);
+        assert_eq!(sta.num_written_regular(), written + 8);
+    }
+}